@@ -1,7 +1,7 @@
 use crate::lexer::Lexer;
 use crate::basic::token::BasicToken;
 
-pub type BasicLexer<'a> = Lexer<'a, BasicToken>;
+pub type BasicLexer<'a> = Lexer<'a, BasicToken<'a>>;
 
 #[cfg(test)]
 mod test {
@@ -10,7 +10,9 @@ mod test {
     use crate::basic::lexer::BasicLexer;
     use crate::basic::token::BasicTokenizer;
     use crate::basic::token::BasicToken;
-    use crate::token::Tokenize;
+    use crate::symbol::Symbol;
+    use crate::token::{CharacterSet, Token, Tokenize};
+    use crate::lexer::Span;
 
     #[rstest]
     #[case("= ", BasicToken::Eq)]
@@ -29,76 +31,214 @@ mod test {
     #[case("begin ", BasicToken::Begin)]
     #[case("define ", BasicToken::Define)]
     fn test_symbols_and_keywords(#[case] input: &str, #[case] expected: BasicToken) {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, input);
 
-        assert_eq!(sut.next_token(), expected);
-        assert_eq!(sut.next_token(), BasicToken::EOF);
+        assert_eq!(sut.next_token().token, expected);
+        assert_eq!(sut.next_token().token, BasicToken::EOF);
     }
 
     #[test]
     fn test_positive_numbers() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, "2345");
 
-        assert_eq!(sut.next_token(), BasicToken::Number(2345));
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::Number(2345));
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
     }
 
     #[test]
     fn test_negative_numbers() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, "-9956");
 
-        assert_eq!(sut.next_token(), BasicToken::Number(-9956));
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::Number(-9956));
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
     }
 
     #[test]
     fn test_name() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, "monkey");
 
-        assert_eq!(sut.next_token(), BasicToken::Name(String::from("monkey")));
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("monkey")));
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
     }
 
     #[test]
     fn test_name_2() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, "<>");
 
-        assert_eq!(sut.next_token(), BasicToken::Name(String::from("<>")));
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("<>")));
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
     }
 
     #[test]
     fn test_multiple_tokens() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, "( define not (boolval) ( if boolval 0 1))");
 
-        assert_eq!(sut.next_token(), BasicToken::LParen);
-        assert_eq!(sut.next_token(), BasicToken::Define);
-        assert_eq!(sut.next_token(), BasicToken::Name(String::from("not")));
-        assert_eq!(sut.next_token(), BasicToken::LParen);
-        assert_eq!(sut.next_token(), BasicToken::Name(String::from("boolval")));
-        assert_eq!(sut.next_token(), BasicToken::RParen);
-        assert_eq!(sut.next_token(), BasicToken::LParen);
-        assert_eq!(sut.next_token(), BasicToken::If);
-        assert_eq!(sut.next_token(), BasicToken::Name(String::from("boolval")));
-        assert_eq!(sut.next_token(), BasicToken::Number(0));
-        assert_eq!(sut.next_token(), BasicToken::Number(1));
-        assert_eq!(sut.next_token(), BasicToken::RParen);
-        assert_eq!(sut.next_token(), BasicToken::RParen);
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::LParen);
+        assert_eq!(sut.next_token().token, BasicToken::Define);
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("not")));
+        assert_eq!(sut.next_token().token, BasicToken::LParen);
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("boolval")));
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::LParen);
+        assert_eq!(sut.next_token().token, BasicToken::If);
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("boolval")));
+        assert_eq!(sut.next_token().token, BasicToken::Number(0));
+        assert_eq!(sut.next_token().token, BasicToken::Number(1));
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
     }
 
     #[test]
     fn test_right_paranthesis_do_not_need_space() {
-        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
         let mut sut = BasicLexer::new(tokenizer, ")");
 
-        assert_eq!(sut.next_token(), BasicToken::RParen);
-        assert_eq!(sut.next_token(), BasicToken::EOF)
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let mut sut = BasicLexer::new(tokenizer, "(set x\n  -12)");
+
+        assert_eq!(sut.next_token().span, Span::new(0, 1, 1, 1));
+        assert_eq!(sut.next_token().span, Span::new(1, 4, 1, 2));
+        assert_eq!(sut.next_token().span, Span::new(5, 6, 1, 6));
+        assert_eq!(sut.next_token().span, Span::new(9, 12, 2, 3));
+        assert_eq!(sut.next_token().span, Span::new(12, 13, 2, 6));
+        assert_eq!(sut.next_token().span, Span::new(13, 13, 2, 7));
+    }
+
+    #[test]
+    fn test_multi_byte_names() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let mut sut = BasicLexer::new(tokenizer, "(set længde größe)");
+
+        assert_eq!(sut.next_token().token, BasicToken::LParen);
+        assert_eq!(sut.next_token().token, BasicToken::Set);
+        let token = sut.next_token();
+        assert_eq!(token.token, BasicToken::Name(Symbol::intern("længde")));
+        assert_eq!(token.span, Span::new(5, 12, 1, 6));
+        let token = sut.next_token();
+        assert_eq!(token.token, BasicToken::Name(Symbol::intern("größe")));
+        assert_eq!(token.span, Span::new(13, 20, 1, 13));
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::EOF)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let tokens: Vec<BasicToken> = BasicLexer::tokenize(tokenizer, "(print 1)")
+            .into_iter()
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            BasicToken::LParen,
+            BasicToken::Print,
+            BasicToken::Number(1),
+            BasicToken::RParen,
+            BasicToken::EOF
+        ]);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn test_number_out_of_range() {
+        use crate::basic::token::BasicErrorKind;
+        use crate::lexer::LexicalError;
+
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let mut sut = BasicLexer::new(tokenizer, "(print -99999999999999999999)");
+
+        assert_eq!(sut.next_token().token, BasicToken::LParen);
+        assert_eq!(sut.next_token().token, BasicToken::Print);
+        assert_eq!(sut.next_token().token, BasicToken::Illegal);
+        assert_eq!(sut.next_token().token, BasicToken::RParen);
+        assert_eq!(sut.next_token().token, BasicToken::EOF);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(BasicErrorKind::IntegerOutOfRange, "-99999999999999999999", Span::new(7, 28, 1, 8)),
+        ]);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_big_number() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let mut sut = BasicLexer::new(tokenizer, "-99999999999999999999");
+
+        assert_eq!(sut.next_token().token, BasicToken::BigNumber("-99999999999999999999".parse().unwrap()));
+        assert_eq!(sut.next_token().token, BasicToken::EOF);
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let tokens: Vec<BasicToken> = BasicLexer::new(tokenizer, "; double it\n(print x) ; done")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            BasicToken::LParen,
+            BasicToken::Print,
+            BasicToken::Name(Symbol::intern("x")),
+            BasicToken::RParen,
+            BasicToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_comments_as_tokens() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let mut sut = BasicLexer::new(tokenizer, "x; the value\r\n").with_comments();
+
+        assert_eq!(sut.next_token().token, BasicToken::Name(Symbol::intern("x")));
+        let token = sut.next_token();
+        assert_eq!(token.token, BasicToken::Comment("; the value"));
+        assert_eq!(token.span, Span::new(1, 12, 1, 2));
+        assert_eq!(sut.next_token().token, BasicToken::EOF);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("(print 1)")]
+    #[case("; not\n(define not (boolval)\r\n  (if boolval 0 1)) ; done\n")]
+    #[case("  (set x -99999999999999999999)\t")]
+    fn test_lossless_round_trip(#[case] input: &str) {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let sut = BasicLexer::new(tokenizer, input).lossless();
+
+        assert_eq!(sut.to_source(), input);
+    }
+
+    #[test]
+    fn test_lossless_text_is_original_source() {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new());
+        let tokens: Vec<_> = BasicLexer::new(tokenizer, "(print x)").lossless().collect();
+
+        assert_eq!(tokens[1].token.token, BasicToken::Print);
+        assert_eq!(tokens[1].token.token.literal(), "PRINT");
+        assert_eq!(tokens[1].text, "print");
+    }
+
+    #[rstest]
+    #[case(CharacterSet::Unicode, vec![BasicToken::Print, BasicToken::Name(Symbol::intern("x")), BasicToken::EOF])]
+    #[case(CharacterSet::Ascii, vec![BasicToken::Name(Symbol::intern("print\u{3000}x")), BasicToken::EOF])]
+    fn test_whitespace_follows_character_set(#[case] character_set: CharacterSet, #[case] expected: Vec<BasicToken>) {
+        let tokenizer: Rc<dyn Tokenize< BasicToken>> = Rc::new(BasicTokenizer::new().with_character_set(character_set));
+        let tokens: Vec<BasicToken> = BasicLexer::new(tokenizer, "print\u{3000}x")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, expected);
     }
 }
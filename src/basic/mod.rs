@@ -1,2 +1,2 @@
-mod token;
-mod lexer;
+pub mod token;
+pub mod lexer;
@@ -1,90 +1,123 @@
-use crate::token::{Token, Tokenize};
-
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum BasicToken {
-    Illegal,
-    EOF,
-    Name(String),
-    Number(isize),
-    Plus,
-    Minus,
-    Asteriks,
-    Slash,
-    LT,
-    GT,
-    Eq,
-    Print,
-    Semicolon,
-    LParen,
-    RParen,
-    Define,
-    If,
-    While,
-    Set,
-    Begin,
+use std::fmt;
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+use crate::symbol::Symbol;
+use crate::define_tokens;
+use crate::token::{CharacterSet, LexicalErrorKind, Tokenize};
+
+define_tokens! {
+    pub enum BasicToken<'a>: BasicErrorKind {
+        values {
+            Comment(&'a str),
+            Name(Symbol),
+            Number(isize),
+            #[cfg(feature = "bigint")]
+            BigNumber(BigInt),
+        }
+        symbols {
+            Plus = "+",
+            Minus = "-",
+            Asteriks = "*",
+            Slash = "/",
+            LT = "<",
+            GT = ">",
+            Eq = "=",
+            LParen = "(",
+            RParen = ")",
+        }
+        keywords {
+            Print = "print" => "PRINT",
+            Define = "define" => "DEFINE",
+            If = "if" => "IF",
+            While = "while" => "WHILE",
+            Set = "set" => "SET",
+            Begin = "begin" => "BEGIN",
+        }
+    }
 }
 
-impl Token for BasicToken {
-    fn literal(&self) -> String {
+#[derive(Debug, Clone, PartialEq)]
+pub enum BasicErrorKind {
+    UnexpectedCharacter(char),
+    IntegerOutOfRange,
+}
+
+impl fmt::Display for BasicErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            BasicToken::Illegal => String::from("ILLEGAL"),
-            BasicToken::EOF => String::from("EOF"),
-            BasicToken::Name(ident) => String::from(ident),
-            BasicToken::Number(value) => String::from(value.to_string().as_str()),
-            BasicToken::Plus => String::from("+"),
-            BasicToken::Minus => String::from("-"),
-            BasicToken::Asteriks => String::from("*"),
-            BasicToken::Slash => String::from("/"),
-            BasicToken::LT => String::from("<"),
-            BasicToken::GT => String::from(">"),
-            BasicToken::Eq => String::from("="),
-            BasicToken::Print => String::from("PRINT"),
-            BasicToken::LParen => String::from("("),
-            BasicToken::RParen => String::from(")"),
-            BasicToken::Define => String::from("DEFINE"),
-            BasicToken::If => String::from("IF"),
-            BasicToken::While => String::from("WHILE"),
-            BasicToken::Set => String::from("SET"),
-            BasicToken::Begin => String::from("BEGIN"),
-            BasicToken::Semicolon => String::from(";"),
+            BasicErrorKind::UnexpectedCharacter(ch) => write!(f, "unexpected character '{}'", ch),
+            BasicErrorKind::IntegerOutOfRange => write!(f, "integer literal out of range"),
         }
     }
 }
 
-pub struct BasicTokenizer {
+impl LexicalErrorKind for BasicErrorKind {
+    fn unexpected_character(ch: char) -> Self {
+        BasicErrorKind::UnexpectedCharacter(ch)
+    }
+}
 
+#[derive(Debug, Clone, Default)]
+pub struct BasicTokenizer {
+    character_set: CharacterSet,
 }
 
 impl BasicTokenizer {
+    pub fn new() -> BasicTokenizer {
+        BasicTokenizer::default()
+    }
+
+    pub fn with_character_set(mut self, character_set: CharacterSet) -> BasicTokenizer {
+        self.character_set = character_set;
+        self
+    }
+
     fn is_digit(ch: char) -> bool{
-        '0' <= ch && ch <= '9'
+        ch.is_ascii_digit()
     }
 
-    fn is_name(ch: char) -> bool{
+    fn is_name(&self, ch: char) -> bool{
         !Self::is_digit(ch) &&
-            !Self::is_white_space(ch) &&
+            !self.character_set.is_whitespace(ch) &&
             ch != '(' && ch != ')' && ch != ';' && ch != '\0'
     }
 
-    fn is_white_space(ch: char) -> bool {
-        ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r'
+    fn is_number(s: &str) -> bool {
+        let digits = s.strip_prefix('-').unwrap_or(s);
+        !digits.is_empty() && digits.chars().all(Self::is_digit)
+    }
+
+    /// Numeric lexemes are always numbers; a value that does not fit in an `isize` is an
+    /// error, or a `BigNumber` token when the `bigint` feature is enabled.
+    fn to_number(s: &str) -> Result<BasicToken<'static>, BasicErrorKind> {
+        match s.parse::<isize>() {
+            Ok(value) => Ok(BasicToken::Number(value)),
+            #[cfg(feature = "bigint")]
+            Err(_) => Ok(BasicToken::BigNumber(s.parse().expect("a numeric lexeme is a valid integer"))),
+            #[cfg(not(feature = "bigint"))]
+            Err(_) => Err(BasicErrorKind::IntegerOutOfRange),
+        }
     }
 }
-impl Tokenize<BasicToken> for BasicTokenizer {
+impl<'a> Tokenize<'a, BasicToken<'a>> for BasicTokenizer {
     fn is_whitespace_character(&self, ch: char) -> bool {
-        Self::is_white_space(ch)
+        self.character_set.is_whitespace(ch)
+    }
+
+    fn is_comment_start_character(&self, ch: char, _: char) -> bool {
+        ch == ';'
     }
 
+    fn is_comment_part_character(&self, ch: char, next: char, _: &str) -> bool {
+        ch != '\n' && ch != '\0' && !(ch == '\r' && next == '\n')
+    }
+
+    /// Operators are only symbols when followed by a space, otherwise they start a name.
     fn is_symbol_start_character(&self, ch: char, next: char) -> bool {
         ch == '('  || ch == ')' || ((ch == '=' || ch == '+' || ch == '-' ||
             ch == '/' || ch == '*' || ch == '<' || ch == '>') && next == ' ')
     }
 
-    fn is_symbol_part_character(&self, _: char, _:char, _: &str) -> bool {
-        false
-    }
-
     fn is_numeric_start_character(&self, ch: char, next: char) -> bool {
         BasicTokenizer::is_digit(ch) || ch == '-' && BasicTokenizer::is_digit(next)
     }
@@ -94,47 +127,22 @@ impl Tokenize<BasicToken> for BasicTokenizer {
     }
 
     fn is_identifier_start_character(&self, ch: char, _: char) -> bool {
-        BasicTokenizer::is_name(ch)
+        self.is_name(ch)
     }
 
     fn is_identifier_part_character(&self, ch: char, _: char, _: &str) -> bool {
-        BasicTokenizer::is_name(ch)
-    }
-
-    fn to_token(&self, s: &str) -> BasicToken {
-        match s {
-            "=" => BasicToken::Eq,
-            "+" => BasicToken::Plus,
-            "-" => BasicToken::Minus,
-            "/" => BasicToken::Slash,
-            "*" => BasicToken::Asteriks,
-            "<" => BasicToken::LT,
-            ">" => BasicToken::GT,
-            ";" => BasicToken::Semicolon,
-            "(" => BasicToken::LParen,
-            ")" => BasicToken::RParen,
-            ";" => BasicToken::Semicolon,
-            "print" => BasicToken::Print,
-            "define" => BasicToken::Define,
-            "if" => BasicToken::If,
-            "while" => BasicToken::While,
-            "set" => BasicToken::Set,
-            "begin" => BasicToken::Begin,
-            x => {
-                if let Ok(value) = x.parse::<isize>() {
-                    BasicToken::Number(value)
-                } else {
-                    BasicToken::Name(x.to_string())
-                }
-            }
-        }
+        self.is_name(ch)
     }
 
-    fn end_of_file_token(&self) -> BasicToken {
-        BasicToken::EOF
+    fn to_value_token(&self, s: &'a str) -> Result<BasicToken<'a>, BasicErrorKind> {
+        if Self::is_number(s) {
+            Self::to_number(s)
+        } else {
+            Ok(BasicToken::Name(Symbol::intern(s)))
+        }
     }
 
-    fn error_token(&self) -> BasicToken {
-        BasicToken::Illegal
+    fn to_comment_token(&self, s: &'a str) -> Result<BasicToken<'a>, BasicErrorKind> {
+        Ok(BasicToken::Comment(s))
     }
 }
@@ -1,67 +1,262 @@
 use std::rc::Rc;
-use crate::token::{Token, Tokenize};
+use crate::lexer::{LexicalError, LocatedToken, LosslessToken, LosslessTokens, Span};
+use crate::token::{LexicalErrorKind, Token, Tokenize};
 
 pub struct Lexer<'a, T> where
     T: Token
 {
-    tokenizer: Rc<dyn Tokenize<T> >,
+    tokenizer: Rc<dyn Tokenize<'a, T>>,
     input: &'a str,
     position: usize,
     read_position: usize,
-    ch: char
+    ch: char,
+    line: usize,
+    column: usize,
+    finished: bool,
+    emit_comments: bool,
+    errors: Vec<LexicalError<T::ErrorKind>>
+}
+
+/// A saved lexer position to backtrack to.
+#[derive(Clone, Copy)]
+struct Cursor {
+    position: usize,
+    read_position: usize,
+    ch: char,
+    line: usize,
+    column: usize
 }
 
 impl<'a, T> Lexer<'a, T>  where
     T: Token
 {
-    pub fn new (tokenizer: Rc<dyn Tokenize<T>>, input: &'a str) -> Lexer<'a, T> {
-        Lexer { tokenizer, input: input, position: 0, read_position: 0, ch: ' ' }
-    }
-    pub fn next_token(&mut self) -> T {
-        self.skip_whitespace();
-        let mut token = self.tokenizer.error_token();
-        if self.ch == '\0' {
-            token = self.tokenizer.end_of_file_token();
+    pub fn new (tokenizer: Rc<dyn Tokenize<'a, T>>, input: &'a str) -> Lexer<'a, T> {
+        let mut lexer = Lexer { tokenizer, input, position: 0, read_position: 0, ch: '\0', line: 1, column: 1, finished: false, emit_comments: false, errors: Vec::new() };
+        lexer.read_next_char();
+        lexer
+    }
+
+    /// Returns comments as tokens instead of skipping them like whitespace.
+    pub fn with_comments(mut self) -> Lexer<'a, T> {
+        self.emit_comments = true;
+        self
+    }
+
+    /// Reads the whole input, returning every token up to and including the end of file token.
+    pub fn tokenize(tokenizer: Rc<dyn Tokenize<'a, T>>, input: &'a str) -> Vec<LocatedToken<T>> {
+        Lexer::new(tokenizer, input).collect()
+    }
+
+    /// Reads the next token. Text that cannot be tokenized is reported through `errors`
+    /// and returned as the tokenizer's error token, after which lexing carries on.
+    pub fn next_token(&mut self) -> LocatedToken<T> {
+        self.skip_trivia(!self.emit_comments, false);
+        self.read_next_token()
+    }
+
+    /// Reads the next token along with the whitespace and comments around it. The leading
+    /// trivia is everything since the previous token, the trailing trivia runs up to the end
+    /// of the token's line, so joining the source of every token reproduces the input.
+    pub fn next_lossless_token(&mut self) -> LosslessToken<'a, T> {
+        let leading_start = self.position;
+        self.skip_trivia(true, false);
+        let leading_trivia = &self.input[leading_start..self.position];
+        let token = self.read_next_token();
+        let text = &self.input[token.span.range()];
+        let trailing_start = self.position;
+        if !self.finished {
+            self.skip_trivia(true, true);
         }
-        else if self.tokenizer.is_symbol_start_character(self.ch, self.peek_next_char()) {
-            token = self.read_token(|r, c, next, s| r.is_symbol_part_character(c, next, s));
+        let trailing_trivia = &self.input[trailing_start..self.position];
+        LosslessToken::new(token, leading_trivia, text, trailing_trivia)
+    }
+
+    /// Iterates over lossless tokens instead of plain ones.
+    pub fn lossless(self) -> LosslessTokens<'a, T> {
+        LosslessTokens::new(self)
+    }
+
+    /// Whether the end of file token has been returned.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn read_next_token(&mut self) -> LocatedToken<T> {
+        let start = self.position;
+        let (line, column) = (self.line, self.column);
+        let result = if self.ch == '\0' {
+            self.finished = true;
+            Ok(self.tokenizer.end_of_file_token())
+        } else if self.tokenizer.is_comment_start_character(self.ch, self.peek_next_char()) {
+            self.read_comment()
+        } else if self.tokenizer.is_string_start_character(self.ch, self.peek_next_char()) {
+            self.read_token(|r, c, next, s| r.is_string_part_character(c, next, s))
+        } else if self.tokenizer.is_symbol_start_character(self.ch, self.peek_next_char()) {
+            self.read_symbol()
         } else if self.tokenizer.is_numeric_start_character(self.ch, self.peek_next_char()) {
-            token = self.read_token(|r, c, next, s| r.is_numeric_part_character(c, next, s));
+            self.read_token(|r, c, next, s| r.is_numeric_part_character(c, next, s))
         } else if self.tokenizer.is_identifier_start_character(self.ch, self.peek_next_char()) {
-            token = self.read_token(|r, c, next, s| r.is_identifier_part_character(c, next, s));
-        }
+            self.read_token(|r, c, next, s| r.is_identifier_part_character(c, next, s))
+        } else {
+            Err(T::ErrorKind::unexpected_character(self.ch))
+        };
+        self.finish_token(result, start, line, column)
+    }
+
+    /// The text being tokenized.
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    /// The errors found so far.
+    pub fn errors(&self) -> &[LexicalError<T::ErrorKind>] {
+        &self.errors
+    }
+
+    /// Records an error for a failed token, then moves past the token's last character.
+    fn finish_token(&mut self, result: Result<T, T::ErrorKind>, start: usize, line: usize, column: usize) -> LocatedToken<T> {
+        let span = Span::new(start, self.read_position, line, column);
+        let token = match result {
+            Ok(token) => token,
+            Err(kind) => {
+                let error_span = match kind.range_in_lexeme() {
+                    Some(range) => span.sub_span(&self.input[span.range()], range),
+                    None => span
+                };
+                self.errors.push(LexicalError::new(kind, &self.input[error_span.range()], error_span));
+                self.tokenizer.error_token()
+            }
+        };
 
         self.read_next_char();
-        token
+        LocatedToken::new(token, span)
+    }
+
+    fn read_comment(&mut self) -> Result<T, T::ErrorKind> {
+        let comment = self.read_lexeme(|r, c, next, s| r.is_comment_part_character(c, next, s));
+        self.tokenizer.to_comment_token(comment)
     }
 
-    fn read_token(&mut self, predicate: fn( Rc<dyn Tokenize<T>>, char, char, &str) -> bool) -> T {
+    /// Reads the longest symbol starting at the current character. Symbol characters are read
+    /// for as long as they can extend some symbol, then the cursor backtracks to the end of
+    /// the longest complete symbol seen, so `**=` and `*` can both be in one table. A character
+    /// that only starts longer symbols, like a lone `&` when the table has `&&`, is an error.
+    fn read_symbol(&mut self) -> Result<T, T::ErrorKind> {
         let start = self.position;
-        let mut ch = self.peek_next_char();
-        while predicate(self.tokenizer.clone(), ch, self.peek_next_char(), &self.input[start..self.read_position]) {
+        let first = self.cursor();
+        let mut longest = None;
+        loop {
+            let lexeme = &self.input[start..self.read_position];
+            if self.tokenizer.is_symbol(lexeme) {
+                longest = Some(self.cursor());
+            }
+            if !self.tokenizer.is_symbol_part_character(self.peek_next_char(), self.peek_second_char(), lexeme) {
+                break;
+            }
             self.read_next_char();
-            ch = self.peek_next_char();
         }
-        return self.tokenizer.to_token(&self.input[start..self.read_position]);
+        match longest {
+            Some(cursor) => self.restore(cursor),
+            None => {
+                self.restore(first);
+                return Err(T::ErrorKind::unexpected_character(self.ch));
+            }
+        }
+        self.tokenizer.to_token(&self.input[start..self.read_position])
     }
-    fn skip_whitespace(&mut self) {
-        while self.tokenizer.is_whitespace_character(self.ch) {
+
+    fn read_token(&mut self, predicate: fn(&dyn Tokenize<'a, T>, char, char, &str) -> bool) -> Result<T, T::ErrorKind> {
+        let lexeme = self.read_lexeme(predicate);
+        self.tokenizer.to_token(lexeme)
+    }
+
+    /// Reads from the current character for as long as the predicate accepts the next one.
+    fn read_lexeme(&mut self, predicate: fn(&dyn Tokenize<'a, T>, char, char, &str) -> bool) -> &'a str {
+        let start = self.position;
+        while predicate(self.tokenizer.as_ref(), self.peek_next_char(), self.peek_second_char(), &self.input[start..self.read_position]) {
             self.read_next_char();
         }
+        &self.input[start..self.read_position]
+    }
+
+    /// Skips whitespace, and comments too when `skip_comments` is set. With `stop_at_line_end`
+    /// the line break ending the current line is left unread.
+    fn skip_trivia(&mut self, skip_comments: bool, stop_at_line_end: bool) {
+        loop {
+            while self.tokenizer.is_whitespace_character(self.ch) && !(stop_at_line_end && self.is_at_line_end()) {
+                self.read_next_char();
+            }
+            if !skip_comments || !self.tokenizer.is_comment_start_character(self.ch, self.peek_next_char()) {
+                break;
+            }
+            let (start, line, column) = (self.position, self.line, self.column);
+            let comment = self.read_comment();
+            self.finish_token(comment, start, line, column);
+        }
+    }
+
+    fn cursor(&self) -> Cursor {
+        Cursor { position: self.position, read_position: self.read_position, ch: self.ch, line: self.line, column: self.column }
+    }
+
+    fn restore(&mut self, cursor: Cursor) {
+        self.position = cursor.position;
+        self.read_position = cursor.read_position;
+        self.ch = cursor.ch;
+        self.line = cursor.line;
+        self.column = cursor.column;
+    }
 
+    fn is_at_line_end(&self) -> bool {
+        self.ch == '\n' || self.ch == '\r' && self.peek_next_char() == '\n'
     }
 
+    /// Moves the cursor one character forward. `position` and `read_position` are
+    /// byte offsets of the current character and of the one after it, so every step
+    /// is constant time and the input can be sliced directly.
     fn read_next_char(&mut self) {
-        self.ch = self.peek_next_char();
+        if self.position < self.read_position {
+            // A `\r\n` pair advances the column on `\r` and starts the new line on `\n`,
+            // so both line endings count as a single line break.
+            if self.ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
         self.position = self.read_position;
-        self.read_position += 1;
+        match self.input[self.read_position..].chars().next() {
+            Some(ch) => {
+                self.ch = ch;
+                self.read_position += ch.len_utf8();
+            }
+            None => self.ch = '\0'
+        }
     }
 
     fn peek_next_char(&self)-> char {
-        if let Some(char_at_index) = self.input.chars().nth(self.read_position) {
-            char_at_index
+        self.input[self.read_position..].chars().next().unwrap_or('\0')
+    }
+
+    fn peek_second_char(&self)-> char {
+        let mut chars = self.input[self.read_position..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
+    }
+}
+
+impl<'a, T> Iterator for Lexer<'a, T> where
+    T: Token
+{
+    type Item = LocatedToken<T>;
+
+    /// Yields the tokens of the input, ending after the end of file token.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            None
         } else {
-            '\0'
+            Some(self.next_token())
         }
     }
 }
@@ -0,0 +1,33 @@
+use std::fmt;
+use crate::lexer::Span;
+use crate::token::LexicalErrorKind;
+
+/// An error found while tokenizing, with the offending text and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexicalError<K> where
+    K: LexicalErrorKind
+{
+    pub kind: K,
+    pub lexeme: String,
+    pub span: Span,
+}
+
+impl<K> LexicalError<K> where
+    K: LexicalErrorKind
+{
+    pub fn new(kind: K, lexeme: &str, span: Span) -> LexicalError<K> {
+        LexicalError { kind, lexeme: lexeme.to_string(), span }
+    }
+
+    pub fn message(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl<K> fmt::Display for LexicalError<K> where
+    K: LexicalErrorKind
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
+    }
+}
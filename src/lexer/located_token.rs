@@ -0,0 +1,19 @@
+use crate::lexer::Span;
+use crate::token::Token;
+
+/// A token together with the place in the input it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatedToken<T> where
+    T: Token
+{
+    pub token: T,
+    pub span: Span,
+}
+
+impl<T> LocatedToken<T> where
+    T: Token
+{
+    pub fn new(token: T, span: Span) -> LocatedToken<T> {
+        LocatedToken { token, span }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::lexer::{Lexer, LocatedToken};
+use crate::token::Token;
+
+/// A token with its original source text and the whitespace and comments around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken<'a, T> where
+    T: Token
+{
+    pub token: LocatedToken<T>,
+    pub leading_trivia: &'a str,
+    pub text: &'a str,
+    pub trailing_trivia: &'a str,
+}
+
+impl<'a, T> LosslessToken<'a, T> where
+    T: Token
+{
+    pub fn new(token: LocatedToken<T>, leading_trivia: &'a str, text: &'a str, trailing_trivia: &'a str) -> LosslessToken<'a, T> {
+        LosslessToken { token, leading_trivia, text, trailing_trivia }
+    }
+
+    /// The exact source the token was read from, trivia included.
+    pub fn source(&self) -> String {
+        [self.leading_trivia, self.text, self.trailing_trivia].concat()
+    }
+}
+
+/// Iterator over the lossless tokens of a `Lexer`, ending after the end of file token.
+pub struct LosslessTokens<'a, T> where
+    T: Token
+{
+    lexer: Lexer<'a, T>,
+}
+
+impl<'a, T> LosslessTokens<'a, T> where
+    T: Token
+{
+    pub fn new(lexer: Lexer<'a, T>) -> LosslessTokens<'a, T> {
+        LosslessTokens { lexer }
+    }
+
+    /// Joins the source of the remaining tokens, which reproduces the rest of the input.
+    pub fn to_source(self) -> String {
+        self.map(|t| t.source()).collect()
+    }
+}
+
+impl<'a, T> Iterator for LosslessTokens<'a, T> where
+    T: Token
+{
+    type Item = LosslessToken<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.lexer.is_finished() {
+            None
+        } else {
+            Some(self.lexer.next_lossless_token())
+        }
+    }
+}
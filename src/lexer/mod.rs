@@ -1,3 +1,12 @@
+#[allow(clippy::module_inception)]
 mod lexer;
+mod span;
+mod located_token;
+mod lexical_error;
+mod lossless_token;
 
 pub use self::lexer::Lexer;
+pub use self::span::Span;
+pub use self::located_token::LocatedToken;
+pub use self::lexical_error::LexicalError;
+pub use self::lossless_token::{LosslessToken, LosslessTokens};
@@ -0,0 +1,59 @@
+use std::fmt;
+use std::ops::Range;
+
+/// The location of a piece of source text.
+///
+/// `start` and `end` are byte offsets into the input, `line` and `column` are the
+/// 1-based position of the first character. Columns count characters, not bytes,
+/// and both `\n` and `\r\n` end a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span { start, end, line, column }
+    }
+
+    /// The span from the start of this span to the end of `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start, other.end.max(self.end), self.line, self.column)
+    }
+
+    /// The span of `range`, given in bytes relative to the start of `text`, the source this
+    /// span covers.
+    pub fn sub_span(&self, text: &str, range: Range<usize>) -> Span {
+        let (mut line, mut column) = (self.line, self.column);
+        for ch in text[..range.start].chars() {
+            if ch == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        Span::new(self.start + range.start, self.start + range.end, line, column)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod lexer;
+pub mod token;
+pub mod symbol;
+pub mod monkey;
+pub mod basic;
@@ -1,12 +1,27 @@
-mod lexer;
-mod token;
-mod monkey;
-mod basic;
-
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Result};
+use interpreters::monkey::eval::{stack_size, Environment, Evaluator, Object, DEFAULT_MAX_CALL_DEPTH};
+use interpreters::monkey::lexer::MonkeyLexer;
+use interpreters::monkey::parser::Parser;
+use interpreters::monkey::token::{MonkeyToken, MonkeyTokenizer};
+use interpreters::token::Tokenize;
+
+/// How deeply Monkey calls can nest. The evaluation thread gets the stack this needs.
+const MAX_CALL_DEPTH: usize = DEFAULT_MAX_CALL_DEPTH;
 
 fn main() -> Result<()> {
+    // Lines are evaluated on a thread of their own, whose stack is sized for the calls they
+    // may nest, rather than whatever the main thread was given.
+    let (lines, received) = mpsc::channel();
+    let (done, finished) = mpsc::channel();
+    let evaluation = thread::Builder::new()
+        .stack_size(stack_size(MAX_CALL_DEPTH))
+        .spawn(move || evaluate(received, done))
+        .expect("failed to spawn the evaluation thread");
     // This line creates an Editor with the default configuration options.
     let mut repl = DefaultEditor::new()?;
     // This if statement loads a file with the history of commands
@@ -23,8 +38,10 @@ fn main() -> Result<()> {
         // The readline method returns an Result. Which we now use a match statement to filter the result.
         match readline {
             Ok(line) => {
-                repl.add_history_entry(line.as_str());
-                println!("Line: {}", line);
+                let _ = repl.add_history_entry(line.as_str());
+                if lines.send(line).is_err() || finished.recv().is_err() {
+                    break
+                }
             },
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -43,5 +60,45 @@ fn main() -> Result<()> {
     // Here we are saving the commands into the file. Until now they are stored in memory.
     #[cfg(feature = "with-file-history")]
     repl.save_history("history.txt").unwrap();
+    drop(lines);
+    evaluation.join().expect("the evaluation thread panicked");
     Ok(())
 }
+
+/// Evaluates each line received, then reports that it is done with it.
+fn evaluate(lines: Receiver<String>, done: Sender<()>) {
+    // Bindings made on one line stay visible on the following ones.
+    let environment = Rc::new(RefCell::new(Environment::new()));
+    // Macros live apart from other bindings, since they're only seen while expanding.
+    let macros = Rc::new(RefCell::new(Environment::new()));
+    let mut evaluator = Evaluator::new().with_max_call_depth(MAX_CALL_DEPTH);
+    for line in lines {
+        evaluate_line(&mut evaluator, &line, &environment, &macros);
+        if done.send(()).is_err() {
+            break;
+        }
+    }
+}
+
+fn evaluate_line(evaluator: &mut Evaluator, line: &str, environment: &Rc<RefCell<Environment>>, macros: &Rc<RefCell<Environment>>) {
+    let tokenizer: Rc<dyn Tokenize<MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+    let mut parser = Parser::new(MonkeyLexer::new(tokenizer, line));
+    let program = parser.parse_program();
+    if !parser.errors().is_empty() {
+        for error in parser.errors() {
+            println!("{}", error);
+        }
+        return;
+    }
+    let program = match evaluator.expand_macros(program, macros) {
+        Ok(program) => program,
+        Err(error) => {
+            println!("ERROR: {}", error);
+            return;
+        }
+    };
+    match evaluator.eval_program(&program, environment) {
+        Object::Null => {}
+        result => println!("{}", result)
+    }
+}
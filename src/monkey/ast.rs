@@ -0,0 +1,470 @@
+use std::fmt;
+use crate::lexer::Span;
+use crate::monkey::token::MonkeyToken;
+use crate::symbol::Symbol;
+use crate::token::Token;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Let { name: Identifier, value: Expression },
+    /// Rebinds an existing variable, in the innermost scope that has it.
+    Assign { name: Identifier, value: Expression },
+    Return(Expression),
+    Expression(Expression),
+    While { condition: Expression, body: BlockStatement },
+    /// Runs the body once per element of an array or character of a string.
+    For { variable: Identifier, iterable: Expression, body: BlockStatement },
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+    pub name: Symbol,
+    pub span: Span,
+}
+
+/// An expression and the source it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
+    Identifier(Symbol),
+    Integer(isize),
+    Boolean(bool),
+    String(String),
+    Prefix { operator: PrefixOperator, right: Box<Expression> },
+    Infix { operator: InfixOperator, left: Box<Expression>, right: Box<Expression> },
+    If { condition: Box<Expression>, consequence: BlockStatement, alternative: Option<BlockStatement> },
+    Function { parameters: Vec<Identifier>, body: BlockStatement },
+    /// A macro literal, only meaningful as the value of a top-level `let`.
+    Macro { parameters: Vec<Identifier>, body: BlockStatement },
+    Call { function: Box<Expression>, arguments: Vec<Expression> },
+    Array(Vec<Expression>),
+    Hash(Vec<(Expression, Expression)>),
+    Index { left: Box<Expression>, index: Box<Expression> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixOperator {
+    Not,
+    Negate,
+}
+
+/// How tightly an operator binds, from loosest to tightest. `Primary` is for expressions
+/// that never need parentheses, such as literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Precedence {
+    Lowest,
+    Or,
+    And,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    /// Calls and indexing, which both follow the expression they apply to.
+    Postfix,
+    Primary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfixOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    /// `&&`, which only evaluates its right operand when the left one is truthy.
+    And,
+    /// `||`, which only evaluates its right operand when the left one is falsy.
+    Or,
+}
+
+impl Identifier {
+    pub fn new(name: Symbol, span: Span) -> Identifier {
+        Identifier { name, span }
+    }
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Expression {
+        Expression { kind, span }
+    }
+}
+
+impl PrefixOperator {
+    /// The token the operator is written as.
+    pub fn token(&self) -> MonkeyToken<'static> {
+        match self {
+            PrefixOperator::Not => MonkeyToken::Bang,
+            PrefixOperator::Negate => MonkeyToken::Minus,
+        }
+    }
+}
+
+impl InfixOperator {
+    /// The token the operator is written as.
+    pub fn token(&self) -> MonkeyToken<'static> {
+        match self {
+            InfixOperator::Add => MonkeyToken::Plus,
+            InfixOperator::Subtract => MonkeyToken::Minus,
+            InfixOperator::Multiply => MonkeyToken::Asteriks,
+            InfixOperator::Divide => MonkeyToken::Slash,
+            InfixOperator::Modulo => MonkeyToken::Percent,
+            InfixOperator::LessThan => MonkeyToken::LT,
+            InfixOperator::GreaterThan => MonkeyToken::GT,
+            InfixOperator::LessEqual => MonkeyToken::LTE,
+            InfixOperator::GreaterEqual => MonkeyToken::GTE,
+            InfixOperator::Equal => MonkeyToken::Eq,
+            InfixOperator::NotEqual => MonkeyToken::NotEq,
+            InfixOperator::And => MonkeyToken::And,
+            InfixOperator::Or => MonkeyToken::Or,
+        }
+    }
+
+    pub(crate) fn precedence(&self) -> Precedence {
+        match self {
+            InfixOperator::Or => Precedence::Or,
+            InfixOperator::And => Precedence::And,
+            InfixOperator::Equal | InfixOperator::NotEqual => Precedence::Equals,
+            InfixOperator::LessThan | InfixOperator::GreaterThan |
+                InfixOperator::LessEqual | InfixOperator::GreaterEqual => Precedence::LessGreater,
+            InfixOperator::Add | InfixOperator::Subtract => Precedence::Sum,
+            InfixOperator::Multiply | InfixOperator::Divide | InfixOperator::Modulo => Precedence::Product,
+        }
+    }
+}
+
+impl ExpressionKind {
+    pub(crate) fn precedence(&self) -> Precedence {
+        match self {
+            ExpressionKind::Prefix { .. } => Precedence::Prefix,
+            ExpressionKind::Infix { operator, .. } => operator.precedence(),
+            ExpressionKind::Call { .. } | ExpressionKind::Index { .. } => Precedence::Postfix,
+            _ => Precedence::Primary,
+        }
+    }
+}
+
+/// Writes `node` in the same mode as `f`: `{:#}` when `f` is in alternate mode.
+fn write_node<T: fmt::Display>(f: &mut fmt::Formatter<'_>, node: &T) -> fmt::Result {
+    if f.alternate() {
+        write!(f, "{:#}", node)
+    } else {
+        write!(f, "{}", node)
+    }
+}
+
+/// Writes an operand of an operator, in parentheses when `needs_parentheses` says its own
+/// operator binds too loosely to be written bare.
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Expression, needs_parentheses: bool) -> fmt::Result {
+    if needs_parentheses && !f.alternate() {
+        write!(f, "(")?;
+        write_node(f, operand)?;
+        write!(f, ")")
+    } else {
+        write_node(f, operand)
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, nodes: &[T]) -> fmt::Result {
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write_node(f, node)?;
+    }
+    Ok(())
+}
+
+/// Writes a string literal with the escapes the lexer understands.
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            ch if ch.is_control() => write!(f, "\\u{{{:x}}}", ch as u32)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Prints the program back as source, one statement per line. The alternate form `{:#}`
+/// puts every prefix, infix and index expression in parentheses, making precedence visible.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, statement) in self.statements.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write_node(f, statement)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Let { name, value } => {
+                write!(f, "let {} = ", name)?;
+                write_node(f, value)?;
+            }
+            Statement::Assign { name, value } => {
+                write!(f, "{} = ", name)?;
+                write_node(f, value)?;
+            }
+            Statement::Return(value) => {
+                write!(f, "return ")?;
+                write_node(f, value)?;
+            }
+            Statement::Expression(expression) => write_node(f, expression)?,
+            Statement::While { condition, body } => {
+                write!(f, "while (")?;
+                write_node(f, condition)?;
+                write!(f, ") ")?;
+                return write_node(f, body);
+            }
+            Statement::For { variable, iterable, body } => {
+                write!(f, "for ({} in ", variable)?;
+                write_node(f, iterable)?;
+                write!(f, ") ")?;
+                return write_node(f, body);
+            }
+            Statement::Break => write!(f, "break")?,
+            Statement::Continue => write!(f, "continue")?,
+        }
+        write!(f, ";")
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for statement in &self.statements {
+            write!(f, " ")?;
+            write_node(f, statement)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for PrefixOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.token().literal())
+    }
+}
+
+impl fmt::Display for InfixOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.token().literal())
+    }
+}
+
+/// Prints the expression with only the parentheses its precedence needs, or with all of them
+/// in the alternate form `{:#}`.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precedence = self.kind.precedence();
+        match &self.kind {
+            ExpressionKind::Identifier(name) => write!(f, "{}", name),
+            ExpressionKind::Integer(value) => write!(f, "{}", value),
+            ExpressionKind::Boolean(value) => write!(f, "{}", value),
+            ExpressionKind::String(value) => write_string(f, value),
+            ExpressionKind::Prefix { operator, right } => {
+                if f.alternate() {
+                    write!(f, "(")?;
+                }
+                write!(f, "{}", operator)?;
+                write_operand(f, right, right.kind.precedence() < precedence)?;
+                if f.alternate() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            ExpressionKind::Infix { operator, left, right } => {
+                if f.alternate() {
+                    write!(f, "(")?;
+                }
+                write_operand(f, left, left.kind.precedence() < precedence)?;
+                write!(f, " {} ", operator)?;
+                write_operand(f, right, right.kind.precedence() <= precedence)?;
+                if f.alternate() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            ExpressionKind::If { condition, consequence, alternative } => {
+                write!(f, "if (")?;
+                write_node(f, condition.as_ref())?;
+                write!(f, ") ")?;
+                write_node(f, consequence)?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else ")?;
+                    write_node(f, alternative)?;
+                }
+                Ok(())
+            }
+            ExpressionKind::Function { parameters, body } => {
+                write!(f, "fn(")?;
+                write_list(f, parameters)?;
+                write!(f, ") ")?;
+                write_node(f, body)
+            }
+            ExpressionKind::Macro { parameters, body } => {
+                write!(f, "macro(")?;
+                write_list(f, parameters)?;
+                write!(f, ") ")?;
+                write_node(f, body)
+            }
+            ExpressionKind::Call { function, arguments } => {
+                write_operand(f, function, function.kind.precedence() < precedence)?;
+                write!(f, "(")?;
+                write_list(f, arguments)?;
+                write!(f, ")")
+            }
+            ExpressionKind::Array(elements) => {
+                write!(f, "[")?;
+                write_list(f, elements)?;
+                write!(f, "]")
+            }
+            ExpressionKind::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_node(f, key)?;
+                    write!(f, ": ")?;
+                    write_node(f, value)?;
+                }
+                write!(f, "}}")
+            }
+            ExpressionKind::Index { left, index } => {
+                if f.alternate() {
+                    write!(f, "(")?;
+                }
+                write_operand(f, left, left.kind.precedence() < precedence)?;
+                write!(f, "[")?;
+                write_node(f, index.as_ref())?;
+                write!(f, "]")?;
+                if f.alternate() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use rstest::rstest;
+    use crate::monkey::ast::Program;
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::token::Tokenize;
+
+    fn parse(input: &str) -> Program {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut parser = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = parser.parse_program();
+
+        assert!(parser.errors().is_empty(), "unexpected parse errors: {:?}", parser.errors());
+        program
+    }
+
+    #[rstest]
+    #[case("let   x=5", "let x = 5;")]
+    #[case("return x", "return x;")]
+    #[case("-a * b", "-a * b;")]
+    #[case("(-a) * b", "-a * b;")]
+    #[case("-(a * b)", "-(a * b);")]
+    #[case("a + b + c", "a + b + c;")]
+    #[case("a + (b + c)", "a + (b + c);")]
+    #[case("(a + b) * (c - d)", "(a + b) * (c - d);")]
+    #[case("a * b + c / d", "a * b + c / d;")]
+    #[case("(5 > 4) == (3 < 4)", "5 > 4 == 3 < 4;")]
+    #[case("a || (b && c)", "a || b && c;")]
+    #[case("(a || b) && c <= d % 2", "(a || b) && c <= d % 2;")]
+    #[case("!(true == false)", "!(true == false);")]
+    #[case("(-x)[1]", "(-x)[1];")]
+    #[case("-x[1]", "-x[1];")]
+    #[case("f(a, b + 1)(c)", "f(a, b + 1)(c);")]
+    #[case("(fn(x) { x })(5)", "fn(x) { x; }(5);")]
+    #[case("f(1)[0]", "f(1)[0];")]
+    #[case("(f(1))[0]", "f(1)[0];")]
+    #[case("a[0](1)", "a[0](1);")]
+    #[case("(a[0])(1)", "a[0](1);")]
+    #[case("if (x < y) { x } else { y }", "if (x < y) { x; } else { y; };")]
+    #[case("fn() {}", "fn() { };")]
+    #[case("let m = macro(a, b) { quote(unquote(a) + b) }", "let m = macro(a, b) { quote(unquote(a) + b); };")]
+    #[case("[1, 2 * 3][0]", "[1, 2 * 3][0];")]
+    #[case("{\"one\": 1, true: [2]}", "{\"one\": 1, true: [2]};")]
+    #[case("\"a \\\"quoted\\\"\\n\\ttext \\\\ \\u{7}\"", "\"a \\\"quoted\\\"\\n\\ttext \\\\ \\u{7}\";")]
+    #[case("let a = 1; a", "let a = 1;\na;")]
+    #[case("x   =x+1", "x = x + 1;")]
+    #[case("while (i < 3) { i = i + 1; if (i == 2) { continue } break }", "while (i < 3) { i = i + 1; if (i == 2) { continue; }; break; }")]
+    #[case("for (c in \"abc\") { puts(c) } x", "for (c in \"abc\") { puts(c); }\nx;")]
+    fn test_canonical_form(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(parse(input).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("-a * b", "((-a) * b);")]
+    #[case("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f);")]
+    #[case("let x = !-a;", "let x = (!(-a));")]
+    #[case("add(a * b[2], b[1])", "add((a * (b[2])), (b[1]));")]
+    #[case("if (a + b) { -c }", "if ((a + b)) { (-c); };")]
+    fn test_parenthesised_form(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(format!("{:#}", parse(input)), expected);
+    }
+
+    #[rstest]
+    #[case("let f = fn(a, b) { if (a > b) { return a - (b - 1); } else { [a, b][0] } };")]
+    #[case("-(-5) + !(!true) * (2 - -3)")]
+    #[case("{1 + 2: \"x\", \"y\": fn() { }}[3]")]
+    #[case("a / (b * c) - (d - e) < f == (g != h)")]
+    #[case("f(1)[0]")]
+    #[case("a[0](1)")]
+    fn test_printed_source_parses_to_the_same_program(#[case] input: &str) {
+        let printed = parse(input).to_string();
+
+        assert_eq!(format!("{:#}", parse(&printed)), format!("{:#}", parse(input)));
+        assert_eq!(parse(&printed).to_string(), printed);
+    }
+}
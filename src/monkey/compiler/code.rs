@@ -0,0 +1,273 @@
+use std::fmt;
+
+/// The name of an opcode and the width in bytes of each of its operands.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+/// Defines `Opcode`, numbering opcodes in the order they are listed, with the operand widths
+/// of each one.
+macro_rules! define_opcodes {
+    ( $( $(#[$attr:meta])* $opcode:ident [$($width:literal),*] ),* $(,)? ) => {
+        /// The first byte of an instruction, which says what it does and how many operand
+        /// bytes follow. Operands are unsigned and big-endian.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $( $(#[$attr])* $opcode, )*
+        }
+
+        impl Opcode {
+            const ALL: &'static [Opcode] = &[ $( Opcode::$opcode, )* ];
+
+            pub fn definition(self) -> &'static Definition {
+                match self {
+                    $( Opcode::$opcode => &Definition {
+                        name: concat!("Op", stringify!($opcode)),
+                        operand_widths: &[$($width),*],
+                    }, )*
+                }
+            }
+        }
+    };
+}
+
+define_opcodes! {
+    /// Pushes the constant at the operand's index in the pool.
+    Constant [2],
+    /// Drops the top of the stack.
+    Pop [],
+    Add [],
+    Subtract [],
+    Multiply [],
+    Divide [],
+    Modulo [],
+    True [],
+    False [],
+    Null [],
+    Equal [],
+    NotEqual [],
+    /// Compares the value below the top of the stack with the top one.
+    GreaterThan [],
+    GreaterEqual [],
+    LessThan [],
+    LessEqual [],
+    /// Negates an integer.
+    Minus [],
+    /// Pushes whether the popped value is falsy.
+    Bang [],
+    /// Continues at the operand's offset.
+    Jump [2],
+    /// Pops a value and continues at the operand's offset if it is falsy.
+    JumpNotTruthy [2],
+    GetGlobal [2],
+    SetGlobal [2],
+    GetLocal [1],
+    SetLocal [1],
+    /// Pops a value into a new cell, which it puts in the operand's local slot.
+    MakeCell [1],
+    /// Pushes the value in the cell of the operand's local slot.
+    GetCell [1],
+    /// Pops a value into the cell of the operand's local slot.
+    SetCell [1],
+    GetBuiltin [1],
+    /// Pushes one of the values the running closure captured, which is a cell for a variable
+    /// kept in one.
+    GetFree [1],
+    /// Pushes the value in the cell of one of the variables the running closure captured.
+    GetFreeCell [1],
+    /// Pops a value into the cell of one of the variables the running closure captured.
+    SetFreeCell [1],
+    /// Pushes the running closure, for a function that calls itself by its name.
+    CurrentClosure [],
+    /// Builds an array from the operand's number of values.
+    Array [2],
+    /// Builds a hash from the operand's number of values, alternating keys and values.
+    Hash [2],
+    Index [],
+    /// Calls the function below the operand's number of arguments.
+    Call [1],
+    /// Returns the popped value from the running function.
+    ReturnValue [],
+    /// Returns `null` from the running function.
+    Return [],
+    /// Makes a closure of the compiled function at the first operand's index in the pool,
+    /// capturing the second operand's number of values from the stack.
+    Closure [2, 1],
+    /// Replaces an array or string with an iterator over its elements or characters.
+    Iterate [],
+    /// Pushes the next element of the iterator on top of the stack, or pops the iterator
+    /// and continues at the operand's offset once it has none left.
+    Next [2],
+}
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        Opcode::ALL.get(byte as usize).copied()
+    }
+}
+
+/// The largest operand that fits in `width` bytes.
+pub fn max_operand(width: usize) -> usize {
+    (1 << (8 * width)) - 1
+}
+
+/// Encodes an instruction. Operands must fit their widths.
+pub fn make(opcode: Opcode, operands: &[usize]) -> Vec<u8> {
+    let widths = opcode.definition().operand_widths;
+    assert_eq!(operands.len(), widths.len(), "wrong number of operands for {}", opcode.definition().name);
+    let mut instruction = vec![opcode as u8];
+    for (&operand, &width) in operands.iter().zip(widths) {
+        assert!(operand <= max_operand(width), "operand {} of {} is wider than {} bytes",
+                operand, opcode.definition().name, width);
+        instruction.extend_from_slice(&operand.to_be_bytes()[usize::BITS as usize / 8 - width..]);
+    }
+    instruction
+}
+
+/// Decodes the operands following an opcode, returning them with the number of bytes read.
+pub fn read_operands(definition: &Definition, bytes: &[u8]) -> (Vec<usize>, usize) {
+    let mut offset = 0;
+    let operands = definition.operand_widths.iter().map(|&width| {
+        let operand = bytes[offset..offset + width].iter().fold(0, |operand, &byte| operand << 8 | byte as usize);
+        offset += width;
+        operand
+    }).collect();
+    (operands, offset)
+}
+
+/// Encoded instructions, back to back.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Instructions(Vec<u8>);
+
+impl Instructions {
+    pub fn new() -> Instructions {
+        Instructions::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Appends an instruction, returning its offset.
+    pub fn push(&mut self, instruction: &[u8]) -> usize {
+        let position = self.0.len();
+        self.0.extend_from_slice(instruction);
+        position
+    }
+
+    /// Replaces the instruction at `position` with one of the same length.
+    pub fn replace(&mut self, position: usize, instruction: &[u8]) {
+        self.0[position..position + instruction.len()].copy_from_slice(instruction);
+    }
+
+    /// Drops everything from `length` on.
+    pub fn truncate(&mut self, length: usize) {
+        self.0.truncate(length);
+    }
+}
+
+impl FromIterator<Vec<u8>> for Instructions {
+    fn from_iter<I: IntoIterator<Item=Vec<u8>>>(instructions: I) -> Instructions {
+        Instructions(instructions.into_iter().flatten().collect())
+    }
+}
+
+/// Disassembles the instructions, one per line, each preceded by its offset.
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut position = 0;
+        while position < self.0.len() {
+            let Some(opcode) = Opcode::from_byte(self.0[position]) else {
+                writeln!(f, "{:04} ERROR: unknown opcode {}", position, self.0[position])?;
+                position += 1;
+                continue;
+            };
+            let definition = opcode.definition();
+            let (operands, read) = read_operands(definition, &self.0[position + 1..]);
+            write!(f, "{:04} {}", position, definition.name)?;
+            for operand in operands {
+                write!(f, " {}", operand)?;
+            }
+            writeln!(f)?;
+            position += 1 + read;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+    use crate::monkey::compiler::{make, read_operands, Instructions, Opcode};
+
+    #[rstest]
+    #[case(Opcode::Constant, &[65534], &[Opcode::Constant as u8, 255, 254])]
+    #[case(Opcode::Add, &[], &[Opcode::Add as u8])]
+    #[case(Opcode::GetLocal, &[255], &[Opcode::GetLocal as u8, 255])]
+    #[case(Opcode::Closure, &[65534, 255], &[Opcode::Closure as u8, 255, 254, 255])]
+    fn test_make(#[case] opcode: Opcode, #[case] operands: &[usize], #[case] expected: &[u8]) {
+        assert_eq!(make(opcode, operands), expected);
+    }
+
+    #[rstest]
+    #[case(Opcode::Constant, &[65535])]
+    #[case(Opcode::GetLocal, &[255])]
+    #[case(Opcode::Closure, &[65535, 255])]
+    fn test_read_operands(#[case] opcode: Opcode, #[case] operands: &[usize]) {
+        let instruction = make(opcode, operands);
+
+        let (read, length) = read_operands(opcode.definition(), &instruction[1..]);
+
+        assert_eq!(read, operands);
+        assert_eq!(length, instruction.len() - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "operand 256 of OpGetLocal is wider than 1 bytes")]
+    fn test_make_rejects_wide_operands() {
+        make(Opcode::GetLocal, &[256]);
+    }
+
+    #[test]
+    fn test_opcodes_round_trip_through_bytes() {
+        for &opcode in Opcode::ALL {
+            assert_eq!(Opcode::from_byte(opcode as u8), Some(opcode));
+        }
+        assert_eq!(Opcode::from_byte(Opcode::ALL.len() as u8), None);
+    }
+
+    #[test]
+    fn test_instructions_display() {
+        let instructions: Instructions = [
+            make(Opcode::Add, &[]),
+            make(Opcode::GetLocal, &[1]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Constant, &[65535]),
+            make(Opcode::Closure, &[65535, 255]),
+        ].into_iter().collect();
+
+        assert_eq!(instructions.to_string(), "0000 OpAdd\n\
+                                              0001 OpGetLocal 1\n\
+                                              0003 OpConstant 2\n\
+                                              0006 OpConstant 65535\n\
+                                              0009 OpClosure 65535 255\n");
+    }
+
+    #[test]
+    fn test_instructions_display_unknown_opcodes() {
+        let instructions: Instructions = [vec![255], make(Opcode::Pop, &[])].into_iter().collect();
+
+        assert_eq!(instructions.to_string(), "0000 ERROR: unknown opcode 255\n0001 OpPop\n");
+    }
+}
@@ -0,0 +1,21 @@
+use std::fmt;
+use crate::lexer::Span;
+
+/// A program the compiler cannot turn into bytecode, and the span of the code at fault.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl CompileError {
+    pub fn new(message: String, span: Span) -> CompileError {
+        CompileError { message, span }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
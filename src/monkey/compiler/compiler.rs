@@ -0,0 +1,1526 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+use crate::lexer::Span;
+use crate::monkey::ast::{BlockStatement, Expression, ExpressionKind, Identifier, InfixOperator, PrefixOperator, Program, Statement};
+use crate::monkey::compiler::{make, max_operand, Binding, CompileError, CompiledFunction, Constant, Instructions, Opcode, Scope, SymbolTable};
+use crate::monkey::eval::Builtins;
+use crate::symbol::Symbol;
+
+/// The operand of a jump until its target is known.
+const UNPATCHED: usize = 0xFFFF;
+
+/// A compiled program: the instructions of its top level and the constants they refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Constant>,
+}
+
+/// Compiles Monkey programs to bytecode. Macros must have been expanded beforehand.
+/// Compiling several programs with one compiler appends them, so later programs see the
+/// globals and constants of earlier ones.
+pub struct Compiler {
+    constants: Vec<Constant>,
+    symbol_table: SymbolTable,
+    /// The function literals being compiled, innermost last, above the top level.
+    scopes: Vec<CompilationScope>,
+    /// The code being compiled, to blame in errors.
+    span: Span,
+}
+
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    /// The loops being compiled, innermost last.
+    loops: Vec<Loop>,
+    /// The number of values on the stack that enclosing expressions have yet to consume.
+    temporaries: usize,
+}
+
+struct Loop {
+    /// Where `continue` jumps to.
+    start: usize,
+    /// The `break` jumps, patched once the end of the loop is known.
+    breaks: Vec<usize>,
+    /// The `temporaries` of the body, which a `break` or `continue` pops down to.
+    temporaries: usize,
+}
+
+impl Default for Compiler {
+    fn default() -> Compiler {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    /// A compiler that knows the standard built-in functions.
+    pub fn new() -> Compiler {
+        Compiler::with_builtins(&Builtins::standard())
+    }
+
+    /// A compiler that knows the functions of `builtins`, numbered for `GetBuiltin` in the
+    /// alphabetical order of their names.
+    pub fn with_builtins(builtins: &Builtins) -> Compiler {
+        let mut names: Vec<Symbol> = builtins.names().collect();
+        names.sort_by_key(|name| name.name());
+        let mut symbol_table = SymbolTable::new();
+        for (index, name) in names.into_iter().enumerate() {
+            symbol_table.define_builtin(index, name);
+        }
+        Compiler {
+            constants: Vec::new(),
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            span: Span::default(),
+        }
+    }
+
+    /// Appends the code of a program. A program that does not compile leaves the compiler as
+    /// it was, without the globals, constants or instructions of its first statements.
+    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+        let instructions = self.scopes[0].instructions.len();
+        let constants = self.constants.len();
+        let symbol_table = self.symbol_table.clone();
+        let span = self.span;
+        let result = self.compile_statements(&program.statements);
+        if result.is_err() {
+            self.scopes[0].instructions.truncate(instructions);
+            self.constants.truncate(constants);
+            self.scopes[0].temporaries = 0;
+            self.symbol_table = symbol_table;
+            self.span = span;
+        }
+        result
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode { instructions: self.scopes[0].instructions.clone(), constants: self.constants.clone() }
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("the top level is never left")
+    }
+
+    fn position(&mut self) -> usize {
+        self.scope().instructions.len()
+    }
+
+    fn error(&self, message: String) -> CompileError {
+        CompileError::new(message, self.span)
+    }
+
+    /// Compiles with errors blamed on `span`.
+    fn at<F>(&mut self, span: Span, compile: F) -> Result<(), CompileError>
+    where F: FnOnce(&mut Compiler) -> Result<(), CompileError> {
+        let outer = mem::replace(&mut self.span, span);
+        compile(self)?;
+        self.span = outer;
+        Ok(())
+    }
+
+    /// Appends an instruction, returning its offset.
+    fn emit(&mut self, opcode: Opcode, operands: &[usize]) -> Result<usize, CompileError> {
+        let widths = opcode.definition().operand_widths;
+        for (index, (&operand, &width)) in operands.iter().zip(widths).enumerate() {
+            if operand > max_operand(width) {
+                return Err(self.error(format!("{} (at most {})", limit(opcode, index), max_operand(width))));
+            }
+        }
+        let instruction = make(opcode, operands);
+        Ok(self.scope().instructions.push(&instruction))
+    }
+
+    /// Points the jump at `position` to `target`.
+    fn patch_jump(&mut self, position: usize, target: usize) -> Result<(), CompileError> {
+        if target > max_operand(2) {
+            return Err(self.error(format!("{} (at most {})", limit(Opcode::Jump, 0), max_operand(2))));
+        }
+        let instructions = &mut self.scope().instructions;
+        let opcode = Opcode::from_byte(instructions.bytes()[position]).expect("a jump was emitted here");
+        instructions.replace(position, &make(opcode, &[target]));
+        Ok(())
+    }
+
+    fn add_constant(&mut self, constant: Constant) -> usize {
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    fn load(&mut self, binding: Binding) -> Result<usize, CompileError> {
+        match binding.scope {
+            Scope::Global => self.emit(Opcode::GetGlobal, &[binding.index]),
+            Scope::Local => self.emit(Opcode::GetLocal, &[binding.index]),
+            Scope::Cell => self.emit(Opcode::GetCell, &[binding.index]),
+            Scope::Builtin => self.emit(Opcode::GetBuiltin, &[binding.index]),
+            Scope::Free => self.emit(Opcode::GetFree, &[binding.index]),
+            Scope::FreeCell => self.emit(Opcode::GetFreeCell, &[binding.index]),
+            Scope::Function => self.emit(Opcode::CurrentClosure, &[]),
+        }
+    }
+
+    /// Pushes what a closure captures of a variable: its cell if it is kept in one, so that
+    /// the closure shares it, or else its value.
+    fn capture(&mut self, binding: Binding) -> Result<usize, CompileError> {
+        match binding.scope {
+            Scope::Cell => self.emit(Opcode::GetLocal, &[binding.index]),
+            Scope::FreeCell => self.emit(Opcode::GetFree, &[binding.index]),
+            _ => self.load(binding),
+        }
+    }
+
+    /// Stores to a variable in a new slot, which needs a new cell if it is kept in one.
+    fn bind(&mut self, binding: Binding) -> Result<usize, CompileError> {
+        match binding.scope {
+            Scope::Cell => self.emit(Opcode::MakeCell, &[binding.index]),
+            _ => self.store(binding),
+        }
+    }
+
+    fn store(&mut self, binding: Binding) -> Result<usize, CompileError> {
+        match binding.scope {
+            Scope::Global => self.emit(Opcode::SetGlobal, &[binding.index]),
+            Scope::Local => self.emit(Opcode::SetLocal, &[binding.index]),
+            Scope::Cell => self.emit(Opcode::SetCell, &[binding.index]),
+            Scope::FreeCell => self.emit(Opcode::SetFreeCell, &[binding.index]),
+            // A captured variable that is assigned to is kept in a cell.
+            Scope::Builtin | Scope::Free | Scope::Function => unreachable!("only variables are stored to"),
+        }
+    }
+
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        statements.iter().try_for_each(|statement| self.compile_statement(statement))
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Let { name, value } => {
+                self.compile_named(value, Some(name.name))?;
+                let definitions = self.symbol_table.definitions();
+                let binding = self.symbol_table.define(name.name);
+                let fresh = self.symbol_table.definitions() > definitions;
+                self.at(name.span, |compiler| {
+                    if fresh { compiler.bind(binding) } else { compiler.store(binding) }.map(drop)
+                })?;
+            }
+            Statement::Assign { name, value } => self.compile_assignment(name, value)?,
+            Statement::Return(value) => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Statement::Expression(expression) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            }
+            Statement::While { condition, body } => {
+                let start = self.position();
+                self.compile_expression(condition)?;
+                let exit = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+                let breaks = self.compile_loop_body(start, body)?;
+                self.emit(Opcode::Jump, &[start])?;
+                let end = self.position();
+                for jump in breaks.into_iter().chain([exit]) {
+                    self.patch_jump(jump, end)?;
+                }
+            }
+            // The iterator stays on the stack while the body runs, so `break` jumps to a
+            // `Pop` of it, whereas `Next` pops it itself when it runs out.
+            Statement::For { variable, iterable, body } => {
+                self.compile_expression(iterable)?;
+                self.emit(Opcode::Iterate, &[])?;
+                let next = self.emit(Opcode::Next, &[UNPATCHED])?;
+                let (binding, hidden) = self.symbol_table.shadow(variable.name);
+                self.scope().temporaries += 1;
+                let breaks = self.at(variable.span, |compiler| compiler.bind(binding).map(drop))
+                    .and_then(|_| self.compile_loop_body(next, body));
+                self.scope().temporaries -= 1;
+                self.symbol_table.restore(variable.name, hidden);
+                let breaks = breaks?;
+                self.emit(Opcode::Jump, &[next])?;
+                let exit = self.emit(Opcode::Pop, &[])?;
+                for jump in breaks {
+                    self.patch_jump(jump, exit)?;
+                }
+                let end = self.position();
+                self.patch_jump(next, end)?;
+            }
+            Statement::Break => {
+                if self.scope().loops.is_empty() {
+                    return Err(self.error(String::from("`break` outside of a loop")));
+                }
+                self.pop_temporaries()?;
+                let jump = self.emit(Opcode::Jump, &[UNPATCHED])?;
+                self.scope().loops.last_mut().expect("checked above").breaks.push(jump);
+            }
+            Statement::Continue => match self.scope().loops.last() {
+                Some(innermost) => {
+                    let start = innermost.start;
+                    self.pop_temporaries()?;
+                    self.emit(Opcode::Jump, &[start])?;
+                }
+                None => return Err(self.error(String::from("`continue` outside of a loop")))
+            },
+        }
+        Ok(())
+    }
+
+    fn compile_assignment(&mut self, name: &Identifier, value: &Expression) -> Result<(), CompileError> {
+        let binding = match self.symbol_table.resolve(name.name) {
+            Some(binding) if matches!(binding.scope, Scope::Global | Scope::Local | Scope::Cell | Scope::FreeCell) => binding,
+            // Only the name of an enclosing function is captured without a cell and assigned.
+            Some(Binding { scope: Scope::Function | Scope::Free, .. }) => return Err(CompileError::new(
+                format!("cannot assign to the function `{}` in its own body", name.name), name.span)),
+            _ => return Err(CompileError::new(format!("assignment to undeclared variable: {}", name.name), name.span))
+        };
+        self.compile_expression(value)?;
+        self.at(name.span, |compiler| compiler.store(binding).map(drop))
+    }
+
+    /// Compiles the body of a loop starting at `start`, returning its `break` jumps.
+    fn compile_loop_body(&mut self, start: usize, body: &BlockStatement) -> Result<Vec<usize>, CompileError> {
+        let temporaries = self.scope().temporaries;
+        self.scope().loops.push(Loop { start, breaks: Vec::new(), temporaries });
+        let result = self.compile_statements(&body.statements);
+        let innermost = self.scope().loops.pop().expect("the loop was pushed above");
+        result.map(|_| innermost.breaks)
+    }
+
+    /// Pops the values that expressions in the innermost loop's body left on the stack, before
+    /// a jump out of them.
+    fn pop_temporaries(&mut self) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let count = scope.temporaries - scope.loops.last().expect("jumps are only compiled in loops").temporaries;
+        for _ in 0..count {
+            self.emit(Opcode::Pop, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Compiles a block that leaves the value of its last statement on the stack, or `null`
+    /// when that statement is not an expression.
+    fn compile_block_value(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        self.compile_statements(&block.statements)?;
+        if let Some(Statement::Expression(_)) = block.statements.last() {
+            let end = self.position();
+            self.scope().instructions.truncate(end - 1);
+        } else {
+            self.emit(Opcode::Null, &[])?;
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        self.compile_named(expression, None)
+    }
+
+    /// Compiles an expression whose value stays on the stack while the following operands are
+    /// compiled. The caller takes it off `temporaries` once an instruction consumes it.
+    fn compile_operand(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        self.compile_expression(expression)?;
+        self.scope().temporaries += 1;
+        Ok(())
+    }
+
+    /// Compiles an expression, which is bound to `name` if it is a function literal.
+    fn compile_named(&mut self, expression: &Expression, name: Option<Symbol>) -> Result<(), CompileError> {
+        self.at(expression.span, |compiler| compiler.compile_kind(&expression.kind, name))
+    }
+
+    fn compile_kind(&mut self, kind: &ExpressionKind, name: Option<Symbol>) -> Result<(), CompileError> {
+        match kind {
+            ExpressionKind::Identifier(name) => match self.symbol_table.resolve(*name) {
+                Some(binding) => self.load(binding).map(drop)?,
+                None => return Err(self.error(format!("identifier not found: {}", name)))
+            },
+            ExpressionKind::Integer(value) => {
+                let constant = self.add_constant(Constant::Integer(*value));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            ExpressionKind::Boolean(true) => self.emit(Opcode::True, &[]).map(drop)?,
+            ExpressionKind::Boolean(false) => self.emit(Opcode::False, &[]).map(drop)?,
+            ExpressionKind::String(value) => {
+                let constant = self.add_constant(Constant::String(Rc::from(value.as_str())));
+                self.emit(Opcode::Constant, &[constant])?;
+            }
+            ExpressionKind::Prefix { operator, right } => {
+                self.compile_expression(right)?;
+                match operator {
+                    PrefixOperator::Not => self.emit(Opcode::Bang, &[])?,
+                    PrefixOperator::Negate => self.emit(Opcode::Minus, &[])?,
+                };
+            }
+            ExpressionKind::Infix { operator, left, right } => self.compile_infix(*operator, left, right)?,
+            ExpressionKind::If { condition, consequence, alternative } => {
+                self.compile_expression(condition)?;
+                let skip_consequence = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+                self.compile_block_value(consequence)?;
+                let skip_alternative = self.emit(Opcode::Jump, &[UNPATCHED])?;
+                let alternative_start = self.position();
+                self.patch_jump(skip_consequence, alternative_start)?;
+                match alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => self.emit(Opcode::Null, &[]).map(drop)?,
+                }
+                let end = self.position();
+                self.patch_jump(skip_alternative, end)?;
+            }
+            ExpressionKind::Function { parameters, body } => self.compile_function(name, parameters, body)?,
+            ExpressionKind::Macro { .. } => {
+                return Err(self.error(String::from("macro literal outside of a top-level let")));
+            }
+            ExpressionKind::Call { function, arguments } => {
+                self.compile_operand(function)?;
+                for argument in arguments {
+                    self.compile_operand(argument)?;
+                }
+                self.scope().temporaries -= arguments.len() + 1;
+                self.emit(Opcode::Call, &[arguments.len()])?;
+            }
+            ExpressionKind::Array(elements) => {
+                for element in elements {
+                    self.compile_operand(element)?;
+                }
+                self.scope().temporaries -= elements.len();
+                self.emit(Opcode::Array, &[elements.len()])?;
+            }
+            ExpressionKind::Hash(pairs) => {
+                for (key, value) in pairs {
+                    self.compile_operand(key)?;
+                    self.compile_operand(value)?;
+                }
+                self.scope().temporaries -= pairs.len() * 2;
+                self.emit(Opcode::Hash, &[pairs.len() * 2])?;
+            }
+            ExpressionKind::Index { left, index } => {
+                self.compile_operand(left)?;
+                self.compile_expression(index)?;
+                self.scope().temporaries -= 1;
+                self.emit(Opcode::Index, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_infix(&mut self, operator: InfixOperator, left: &Expression, right: &Expression) -> Result<(), CompileError> {
+        let opcode = match operator {
+            InfixOperator::And | InfixOperator::Or => return self.compile_logical(operator, left, right),
+            InfixOperator::Add => Opcode::Add,
+            InfixOperator::Subtract => Opcode::Subtract,
+            InfixOperator::Multiply => Opcode::Multiply,
+            InfixOperator::Divide => Opcode::Divide,
+            InfixOperator::Modulo => Opcode::Modulo,
+            InfixOperator::GreaterThan => Opcode::GreaterThan,
+            InfixOperator::GreaterEqual => Opcode::GreaterEqual,
+            InfixOperator::LessThan => Opcode::LessThan,
+            InfixOperator::LessEqual => Opcode::LessEqual,
+            InfixOperator::Equal => Opcode::Equal,
+            InfixOperator::NotEqual => Opcode::NotEqual,
+        };
+        self.compile_operand(left)?;
+        self.compile_expression(right)?;
+        self.scope().temporaries -= 1;
+        self.emit(opcode, &[]).map(drop)
+    }
+
+    /// Compiles `&&` or `||` to jumps that skip the right operand when the left one decides
+    /// the result. A right operand that is evaluated is turned into a boolean by `!!`.
+    fn compile_logical(&mut self, operator: InfixOperator, left: &Expression, right: &Expression) -> Result<(), CompileError> {
+        self.compile_expression(left)?;
+        let to_right = self.emit(Opcode::JumpNotTruthy, &[UNPATCHED])?;
+        if operator == InfixOperator::Or {
+            self.emit(Opcode::True, &[])?;
+            let to_end = self.emit(Opcode::Jump, &[UNPATCHED])?;
+            let right_start = self.position();
+            self.patch_jump(to_right, right_start)?;
+            self.compile_expression(right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+            let end = self.position();
+            self.patch_jump(to_end, end)
+        } else {
+            self.compile_expression(right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+            let to_end = self.emit(Opcode::Jump, &[UNPATCHED])?;
+            let short_circuit = self.emit(Opcode::False, &[])?;
+            self.patch_jump(to_right, short_circuit)?;
+            let end = self.position();
+            self.patch_jump(to_end, end)
+        }
+    }
+
+    /// Compiles a function literal to a constant, and emits the closure of it over the free
+    /// variables it refers to.
+    fn compile_function(&mut self, name: Option<Symbol>, parameters: &[Identifier], body: &BlockStatement) -> Result<(), CompileError> {
+        self.scopes.push(CompilationScope::default());
+        self.symbol_table = SymbolTable::enclosed(mem::take(&mut self.symbol_table)).with_cells(shared_variables(parameters, body));
+        let result = self.compile_function_body(name, parameters, body);
+        let scope = self.scopes.pop().expect("the function's scope was pushed above");
+        let free = self.symbol_table.free().to_vec();
+        let locals = self.symbol_table.definitions();
+        let table = mem::take(&mut self.symbol_table);
+        self.symbol_table = table.into_outer().expect("the function's table is enclosed");
+        result?;
+
+        for binding in &free {
+            self.capture(*binding)?;
+        }
+        let function = CompiledFunction { name, instructions: scope.instructions, locals, parameters: parameters.len() };
+        let constant = self.add_constant(Constant::Function(Rc::new(function)));
+        self.emit(Opcode::Closure, &[constant, free.len()]).map(drop)
+    }
+
+    fn compile_function_body(&mut self, name: Option<Symbol>, parameters: &[Identifier], body: &BlockStatement) -> Result<(), CompileError> {
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for parameter in parameters {
+            let (binding, _) = self.symbol_table.shadow(parameter.name);
+            if binding.scope == Scope::Cell {
+                self.emit(Opcode::GetLocal, &[binding.index])?;
+                self.emit(Opcode::MakeCell, &[binding.index])?;
+            }
+        }
+        self.compile_statements(&body.statements)?;
+        match body.statements.last() {
+            Some(Statement::Expression(_)) => {
+                let end = self.position();
+                self.scope().instructions.truncate(end - 1);
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Some(Statement::Return(_)) => {}
+            _ => {
+                self.emit(Opcode::Return, &[])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The variables of a function to keep in cells: those that a closure in it refers to and
+/// that are assigned to, or bound again by a later `let`, which reuses their slot just as the
+/// evaluator sets them again in the same environment. Names are not told apart by scope, so
+/// this may pick variables that need no cell, which only makes them slower.
+fn shared_variables(parameters: &[Identifier], body: &BlockStatement) -> HashSet<Symbol> {
+    let mut usage = Usage::default();
+    usage.bound.extend(parameters.iter().map(|parameter| parameter.name));
+    usage.block(body, false);
+    usage.captured.intersection(&usage.assigned).copied().collect()
+}
+
+/// The names a function body refers to from closures, and the names it assigns to.
+#[derive(Default)]
+struct Usage {
+    captured: HashSet<Symbol>,
+    assigned: HashSet<Symbol>,
+    /// The names the function itself has bound so far.
+    bound: HashSet<Symbol>,
+}
+
+impl Usage {
+    fn block(&mut self, block: &BlockStatement, in_closure: bool) {
+        for statement in &block.statements {
+            self.statement(statement, in_closure);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, in_closure: bool) {
+        match statement {
+            Statement::Let { name, value } => {
+                if !in_closure && !self.bound.insert(name.name) {
+                    self.assigned.insert(name.name);
+                }
+                self.expression(value, in_closure);
+            }
+            Statement::Return(value) | Statement::Expression(value) => {
+                self.expression(value, in_closure);
+            }
+            Statement::Assign { name, value } => {
+                self.assigned.insert(name.name);
+                if in_closure {
+                    self.captured.insert(name.name);
+                }
+                self.expression(value, in_closure);
+            }
+            Statement::While { condition: expression, body } | Statement::For { iterable: expression, body, .. } => {
+                self.expression(expression, in_closure);
+                self.block(body, in_closure);
+            }
+            Statement::Break | Statement::Continue => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, in_closure: bool) {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => {
+                if in_closure {
+                    self.captured.insert(*name);
+                }
+            }
+            ExpressionKind::Integer(_) | ExpressionKind::Boolean(_) | ExpressionKind::String(_) |
+            ExpressionKind::Macro { .. } => {}
+            ExpressionKind::Prefix { right, .. } => self.expression(right, in_closure),
+            ExpressionKind::Infix { left, right, .. } | ExpressionKind::Index { left, index: right } => {
+                self.expression(left, in_closure);
+                self.expression(right, in_closure);
+            }
+            ExpressionKind::If { condition, consequence, alternative } => {
+                self.expression(condition, in_closure);
+                self.block(consequence, in_closure);
+                if let Some(alternative) = alternative {
+                    self.block(alternative, in_closure);
+                }
+            }
+            ExpressionKind::Function { body, .. } => self.block(body, true),
+            ExpressionKind::Call { function, arguments } => {
+                self.expression(function, in_closure);
+                for argument in arguments {
+                    self.expression(argument, in_closure);
+                }
+            }
+            ExpressionKind::Array(elements) => {
+                for element in elements {
+                    self.expression(element, in_closure);
+                }
+            }
+            ExpressionKind::Hash(pairs) => {
+                for (key, value) in pairs {
+                    self.expression(key, in_closure);
+                    self.expression(value, in_closure);
+                }
+            }
+        }
+    }
+}
+
+/// What there are too many of when an operand of `opcode` does not fit.
+fn limit(opcode: Opcode, operand: usize) -> &'static str {
+    match (opcode, operand) {
+        (Opcode::Constant, _) | (Opcode::Closure, 0) => "too many constants",
+        (Opcode::GetGlobal | Opcode::SetGlobal, _) => "too many global variables",
+        (Opcode::GetLocal | Opcode::SetLocal | Opcode::MakeCell | Opcode::GetCell | Opcode::SetCell, _) => {
+            "too many local variables"
+        }
+        (Opcode::GetFree | Opcode::GetFreeCell | Opcode::SetFreeCell | Opcode::Closure, _) => "too many free variables",
+        (Opcode::GetBuiltin, _) => "too many built-in functions",
+        (Opcode::Call, _) => "too many arguments",
+        (Opcode::Array | Opcode::Hash, _) => "too many elements",
+        _ => "program too long",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use rstest::rstest;
+    use crate::lexer::Span;
+    use crate::monkey::compiler::{make, Bytecode, CompileError, Compiler, Constant, Instructions, Opcode as Op};
+    use crate::monkey::eval::{Builtins, Object};
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::symbol::Symbol;
+    use crate::token::Tokenize;
+
+    /// A constant as the tests expect it, with functions shown disassembled.
+    #[derive(Debug, PartialEq)]
+    enum Expected {
+        Integer(isize),
+        String(String),
+        Function(String),
+    }
+
+    fn function(instructions: Vec<Vec<u8>>) -> Expected {
+        Expected::Function(disassemble(instructions))
+    }
+
+    fn disassemble(instructions: Vec<Vec<u8>>) -> String {
+        instructions.into_iter().collect::<Instructions>().to_string()
+    }
+
+    fn try_compile_with(mut compiler: Compiler, input: &str) -> Result<Bytecode, CompileError> {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut parser = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "unexpected parse errors: {:?}", parser.errors());
+
+        compiler.compile(&program)?;
+        Ok(compiler.bytecode())
+    }
+
+    fn compile(input: &str) -> Bytecode {
+        try_compile_with(Compiler::new(), input).unwrap()
+    }
+
+    fn compile_error(input: &str) -> CompileError {
+        try_compile_with(Compiler::new(), input).unwrap_err()
+    }
+
+    fn assert_compiles(input: &str, constants: Vec<Expected>, instructions: Vec<Vec<u8>>) {
+        let bytecode = compile(input);
+
+        assert_eq!(bytecode.instructions.to_string(), disassemble(instructions), "instructions of {}", input);
+        let actual: Vec<Expected> = bytecode.constants.iter().map(|constant| match constant {
+            Constant::Integer(value) => Expected::Integer(*value),
+            Constant::String(value) => Expected::String(value.to_string()),
+            Constant::Function(function) => Expected::Function(function.instructions.to_string()),
+        }).collect();
+        assert_eq!(actual, constants, "constants of {}", input);
+    }
+
+    #[rstest]
+    #[case("1 + 2", Op::Add)]
+    #[case("1 - 2", Op::Subtract)]
+    #[case("1 * 2", Op::Multiply)]
+    #[case("1 / 2", Op::Divide)]
+    #[case("1 % 2", Op::Modulo)]
+    #[case("1 > 2", Op::GreaterThan)]
+    #[case("1 >= 2", Op::GreaterEqual)]
+    #[case("1 < 2", Op::LessThan)]
+    #[case("1 <= 2", Op::LessEqual)]
+    #[case("1 == 2", Op::Equal)]
+    #[case("1 != 2", Op::NotEqual)]
+    fn test_infix_operators(#[case] input: &str, #[case] opcode: Op) {
+        assert_compiles(input, vec![Expected::Integer(1), Expected::Integer(2)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(opcode, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_comparisons_evaluate_their_operands_from_left_to_right() {
+        let input = "let a = 0; let f = fn() { a = a + 1 }; let g = fn() { a = a * 10 }; f() < g()";
+
+        assert_compiles(input, vec![
+            Expected::Integer(0),
+            Expected::Integer(1),
+            function(vec![
+                make(Op::GetGlobal, &[0]),
+                make(Op::Constant, &[1]),
+                make(Op::Add, &[]),
+                make(Op::SetGlobal, &[0]),
+                make(Op::Return, &[]),
+            ]),
+            Expected::Integer(10),
+            function(vec![
+                make(Op::GetGlobal, &[0]),
+                make(Op::Constant, &[3]),
+                make(Op::Multiply, &[]),
+                make(Op::SetGlobal, &[0]),
+                make(Op::Return, &[]),
+            ]),
+        ], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Closure, &[2, 0]),
+            make(Op::SetGlobal, &[1]),
+            make(Op::Closure, &[4, 0]),
+            make(Op::SetGlobal, &[2]),
+            make(Op::GetGlobal, &[1]),
+            make(Op::Call, &[0]),
+            make(Op::GetGlobal, &[2]),
+            make(Op::Call, &[0]),
+            make(Op::LessThan, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_compiles("1; 2", vec![Expected::Integer(1), Expected::Integer(2)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Pop, &[]),
+            make(Op::Constant, &[1]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("-1", vec![Expected::Integer(1)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Minus, &[]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("!true == false", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::Bang, &[]),
+            make(Op::False, &[]),
+            make(Op::Equal, &[]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("\"mon\" + \"key\"", vec![Expected::String(String::from("mon")), Expected::String(String::from("key"))], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Add, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_logical_operators_jump_over_their_right_operand() {
+        assert_compiles("true && false", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[10]),
+            make(Op::False, &[]),
+            make(Op::Bang, &[]),
+            make(Op::Bang, &[]),
+            make(Op::Jump, &[11]),
+            make(Op::False, &[]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("true || false", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[8]),
+            make(Op::True, &[]),
+            make(Op::Jump, &[11]),
+            make(Op::False, &[]),
+            make(Op::Bang, &[]),
+            make(Op::Bang, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        assert_compiles("if (true) { 10 }; 3333;", vec![Expected::Integer(10), Expected::Integer(3333)], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[10]),
+            make(Op::Constant, &[0]),
+            make(Op::Jump, &[11]),
+            make(Op::Null, &[]),
+            make(Op::Pop, &[]),
+            make(Op::Constant, &[1]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("if (true) { 10 } else { 20 }; 3333;",
+                        vec![Expected::Integer(10), Expected::Integer(20), Expected::Integer(3333)], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[10]),
+            make(Op::Constant, &[0]),
+            make(Op::Jump, &[13]),
+            make(Op::Constant, &[1]),
+            make(Op::Pop, &[]),
+            make(Op::Constant, &[2]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_blocks_without_a_value_give_null() {
+        assert_compiles("if (true) { }", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[8]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[9]),
+            make(Op::Null, &[]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("if (true) { let a = 1; }", vec![Expected::Integer(1)], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[14]),
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[15]),
+            make(Op::Null, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        assert_compiles("let one = 1; let two = one; two;", vec![Expected::Integer(1)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::SetGlobal, &[1]),
+            make(Op::GetGlobal, &[1]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("let a = 1; let a = a + 1;", vec![Expected::Integer(1), Expected::Integer(1)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Add, &[]),
+            make(Op::SetGlobal, &[0]),
+        ]);
+    }
+
+    #[test]
+    fn test_arrays_hashes_and_indexes() {
+        assert_compiles("[]; {}", vec![], vec![
+            make(Op::Array, &[0]),
+            make(Op::Pop, &[]),
+            make(Op::Hash, &[0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("[1, 2 * 3][0]", (1..=3).chain([0]).map(Expected::Integer).collect(), vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Constant, &[2]),
+            make(Op::Multiply, &[]),
+            make(Op::Array, &[2]),
+            make(Op::Constant, &[3]),
+            make(Op::Index, &[]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("{1: 2 + 3, 4: 5}[4]", (1..=5).chain([4]).map(Expected::Integer).collect(), vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Constant, &[2]),
+            make(Op::Add, &[]),
+            make(Op::Constant, &[3]),
+            make(Op::Constant, &[4]),
+            make(Op::Hash, &[4]),
+            make(Op::Constant, &[5]),
+            make(Op::Index, &[]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[rstest]
+    #[case("fn() { return 5 + 10 }")]
+    #[case("fn() { 5 + 10 }")]
+    fn test_functions_return_their_last_expression(#[case] input: &str) {
+        assert_compiles(input, vec![Expected::Integer(5), Expected::Integer(10), function(vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Add, &[]),
+            make(Op::ReturnValue, &[]),
+        ])], vec![
+            make(Op::Closure, &[2, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_functions_without_a_value_return_null() {
+        assert_compiles("fn() { }", vec![function(vec![make(Op::Return, &[])])], vec![
+            make(Op::Closure, &[0, 0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn() { let a = 1; }", vec![Expected::Integer(1), function(vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetLocal, &[0]),
+            make(Op::Return, &[]),
+        ])], vec![
+            make(Op::Closure, &[1, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_calls() {
+        assert_compiles("let oneArg = fn(a) { a }; oneArg(24);", vec![
+            function(vec![make(Op::GetLocal, &[0]), make(Op::ReturnValue, &[])]),
+            Expected::Integer(24),
+        ], vec![
+            make(Op::Closure, &[0, 0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Call, &[1]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn(a, b, c) { a; b; c }(24, 25, 26)", vec![
+            function(vec![
+                make(Op::GetLocal, &[0]),
+                make(Op::Pop, &[]),
+                make(Op::GetLocal, &[1]),
+                make(Op::Pop, &[]),
+                make(Op::GetLocal, &[2]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(24),
+            Expected::Integer(25),
+            Expected::Integer(26),
+        ], vec![
+            make(Op::Closure, &[0, 0]),
+            make(Op::Constant, &[1]),
+            make(Op::Constant, &[2]),
+            make(Op::Constant, &[3]),
+            make(Op::Call, &[3]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_compiled_functions_know_their_name_and_slots() {
+        let bytecode = compile("let f = fn(a, b) { let c = a; }; fn(a, a) { }");
+
+        let [Constant::Function(named), Constant::Function(anonymous)] = &bytecode.constants[..] else {
+            panic!("expected two functions, got {:?}", bytecode.constants);
+        };
+        assert_eq!((named.name, named.locals, named.parameters), (Some(Symbol::intern("f")), 3, 2));
+        assert_eq!((anonymous.name, anonymous.locals, anonymous.parameters), (None, 2, 2));
+    }
+
+    #[test]
+    fn test_let_statement_scopes() {
+        assert_compiles("let num = 55; fn() { num }", vec![Expected::Integer(55), function(vec![
+            make(Op::GetGlobal, &[0]),
+            make(Op::ReturnValue, &[]),
+        ])], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Closure, &[1, 0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn() { let num = 55; num }", vec![Expected::Integer(55), function(vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetLocal, &[0]),
+            make(Op::GetLocal, &[0]),
+            make(Op::ReturnValue, &[]),
+        ])], vec![
+            make(Op::Closure, &[1, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_compiles("len([]); push([], 1); fn() { first }", vec![Expected::Integer(1), function(vec![
+            make(Op::GetBuiltin, &[0]),
+            make(Op::ReturnValue, &[]),
+        ])], vec![
+            make(Op::GetBuiltin, &[2]),
+            make(Op::Array, &[0]),
+            make(Op::Call, &[1]),
+            make(Op::Pop, &[]),
+            make(Op::GetBuiltin, &[3]),
+            make(Op::Array, &[0]),
+            make(Op::Constant, &[0]),
+            make(Op::Call, &[2]),
+            make(Op::Pop, &[]),
+            make(Op::Closure, &[1, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_custom_builtins() {
+        let builtins = Builtins::new()
+            .with("zero", Some(0), |_| Ok(Object::Integer(0)))
+            .with("answer", Some(0), |_| Ok(Object::Integer(42)));
+
+        let bytecode = try_compile_with(Compiler::with_builtins(&builtins), "zero; answer").unwrap();
+
+        assert_eq!(bytecode.instructions.to_string(), disassemble(vec![
+            make(Op::GetBuiltin, &[1]),
+            make(Op::Pop, &[]),
+            make(Op::GetBuiltin, &[0]),
+            make(Op::Pop, &[]),
+        ]));
+        assert_eq!(try_compile_with(Compiler::with_builtins(&builtins), "len").unwrap_err().message,
+                   "identifier not found: len");
+    }
+
+    #[test]
+    fn test_closures() {
+        assert_compiles("fn(a) { fn(b) { a + b } }", vec![
+            function(vec![
+                make(Op::GetFree, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Add, &[]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            function(vec![
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[0, 1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[1, 0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn(a) { fn(b) { fn(c) { a + b + c } } }", vec![
+            function(vec![
+                make(Op::GetFree, &[0]),
+                make(Op::GetFree, &[1]),
+                make(Op::Add, &[]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Add, &[]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            function(vec![
+                make(Op::GetFree, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[0, 2]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            function(vec![
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[1, 1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[2, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        assert_compiles("let countDown = fn(x) { countDown(x - 1); }; countDown(1);", vec![
+            Expected::Integer(1),
+            function(vec![
+                make(Op::CurrentClosure, &[]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Constant, &[0]),
+                make(Op::Subtract, &[]),
+                make(Op::Call, &[1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(1),
+        ], vec![
+            make(Op::Closure, &[1, 0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Constant, &[2]),
+            make(Op::Call, &[1]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("let wrapper = fn() { let countDown = fn(x) { countDown(x - 1); }; countDown(1); };", vec![
+            Expected::Integer(1),
+            function(vec![
+                make(Op::CurrentClosure, &[]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Constant, &[0]),
+                make(Op::Subtract, &[]),
+                make(Op::Call, &[1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(1),
+            function(vec![
+                make(Op::Closure, &[1, 0]),
+                make(Op::SetLocal, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Constant, &[2]),
+                make(Op::Call, &[1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[3, 0]),
+            make(Op::SetGlobal, &[0]),
+        ]);
+    }
+
+    #[test]
+    fn test_assignment() {
+        assert_compiles("let x = 1; x = 2;", vec![Expected::Integer(1), Expected::Integer(2)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::SetGlobal, &[0]),
+        ]);
+        assert_compiles("let counter = fn() { let n = 0; fn() { n = n + 1; n } };", vec![
+            Expected::Integer(0),
+            Expected::Integer(1),
+            function(vec![
+                make(Op::GetFreeCell, &[0]),
+                make(Op::Constant, &[1]),
+                make(Op::Add, &[]),
+                make(Op::SetFreeCell, &[0]),
+                make(Op::GetFreeCell, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            function(vec![
+                make(Op::Constant, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[2, 1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[3, 0]),
+            make(Op::SetGlobal, &[0]),
+        ]);
+    }
+
+    #[test]
+    fn test_closures_share_the_variables_they_assign_to() {
+        assert_compiles("let f = fn() { let n = 0; let inc = fn() { n = n + 1 }; inc(); n }; f()", vec![
+            Expected::Integer(0),
+            Expected::Integer(1),
+            function(vec![
+                make(Op::GetFreeCell, &[0]),
+                make(Op::Constant, &[1]),
+                make(Op::Add, &[]),
+                make(Op::SetFreeCell, &[0]),
+                make(Op::Return, &[]),
+            ]),
+            function(vec![
+                make(Op::Constant, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[2, 1]),
+                make(Op::SetLocal, &[1]),
+                make(Op::GetLocal, &[1]),
+                make(Op::Call, &[0]),
+                make(Op::Pop, &[]),
+                make(Op::GetCell, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[3, 0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Call, &[0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn(n) { [fn() { n }, fn() { fn() { n = 1 } }]; n = 2 }", vec![
+            function(vec![
+                make(Op::GetFreeCell, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(1),
+            function(vec![
+                make(Op::Constant, &[1]),
+                make(Op::SetFreeCell, &[0]),
+                make(Op::Return, &[]),
+            ]),
+            function(vec![
+                make(Op::GetFree, &[0]),
+                make(Op::Closure, &[2, 1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(2),
+            function(vec![
+                make(Op::GetLocal, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[0, 1]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[3, 1]),
+                make(Op::Array, &[2]),
+                make(Op::Pop, &[]),
+                make(Op::Constant, &[4]),
+                make(Op::SetCell, &[0]),
+                make(Op::Return, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[5, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_closures_see_variables_bound_again() {
+        assert_compiles("fn() { let x = 1; let f = fn() { x }; let x = 2; f() }", vec![
+            Expected::Integer(1),
+            function(vec![
+                make(Op::GetFreeCell, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(2),
+            function(vec![
+                make(Op::Constant, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[1, 1]),
+                make(Op::SetLocal, &[1]),
+                make(Op::Constant, &[2]),
+                make(Op::SetCell, &[0]),
+                make(Op::GetLocal, &[1]),
+                make(Op::Call, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[3, 0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn(x) { let f = fn() { x }; let x = 2; f }", vec![
+            function(vec![
+                make(Op::GetFreeCell, &[0]),
+                make(Op::ReturnValue, &[]),
+            ]),
+            Expected::Integer(2),
+            function(vec![
+                make(Op::GetLocal, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[0, 1]),
+                make(Op::SetLocal, &[1]),
+                make(Op::Constant, &[1]),
+                make(Op::SetCell, &[0]),
+                make(Op::GetLocal, &[1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[2, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_only_new_slots_get_a_new_cell() {
+        assert_compiles("fn() { let n = 0; let n = 1; fn() { n = 2 } }", vec![
+            Expected::Integer(0),
+            Expected::Integer(1),
+            Expected::Integer(2),
+            function(vec![
+                make(Op::Constant, &[2]),
+                make(Op::SetFreeCell, &[0]),
+                make(Op::Return, &[]),
+            ]),
+            function(vec![
+                make(Op::Constant, &[0]),
+                make(Op::MakeCell, &[0]),
+                make(Op::Constant, &[1]),
+                make(Op::SetCell, &[0]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[3, 1]),
+                make(Op::ReturnValue, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[4, 0]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn() { let n = 0; for (i in []) { fn() { i = n } } }", vec![
+            Expected::Integer(0),
+            function(vec![
+                make(Op::GetFree, &[1]),
+                make(Op::SetFreeCell, &[0]),
+                make(Op::Return, &[]),
+            ]),
+            function(vec![
+                make(Op::Constant, &[0]),
+                make(Op::SetLocal, &[0]),
+                make(Op::Array, &[0]),
+                make(Op::Iterate, &[]),
+                make(Op::Next, &[27]),
+                make(Op::MakeCell, &[1]),
+                make(Op::GetLocal, &[1]),
+                make(Op::GetLocal, &[0]),
+                make(Op::Closure, &[1, 2]),
+                make(Op::Pop, &[]),
+                make(Op::Jump, &[9]),
+                make(Op::Pop, &[]),
+                make(Op::Return, &[]),
+            ]),
+        ], vec![
+            make(Op::Closure, &[2, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_while_loops() {
+        assert_compiles("let i = 0; while (i < 3) { i = i + 1; }",
+                        vec![Expected::Integer(0), Expected::Integer(3), Expected::Integer(1)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::LessThan, &[]),
+            make(Op::JumpNotTruthy, &[29]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Constant, &[2]),
+            make(Op::Add, &[]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Jump, &[6]),
+        ]);
+    }
+
+    #[test]
+    fn test_break_and_continue_in_while_loops() {
+        assert_compiles("while (true) { break; continue; }", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[13]),
+            make(Op::Jump, &[13]),
+            make(Op::Jump, &[0]),
+            make(Op::Jump, &[0]),
+        ]);
+        assert_compiles("while (true) { if (true) { break; } }", vec![], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[20]),
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[15]),
+            make(Op::Jump, &[20]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[16]),
+            make(Op::Null, &[]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[0]),
+        ]);
+    }
+
+    #[test]
+    fn test_for_loops() {
+        assert_compiles("for (x in [1, 2]) { x; }", vec![Expected::Integer(1), Expected::Integer(2)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Constant, &[1]),
+            make(Op::Array, &[2]),
+            make(Op::Iterate, &[]),
+            make(Op::Next, &[24]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[10]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("for (x in []) { continue; }", vec![], vec![
+            make(Op::Array, &[0]),
+            make(Op::Iterate, &[]),
+            make(Op::Next, &[17]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Jump, &[4]),
+            make(Op::Jump, &[4]),
+            make(Op::Pop, &[]),
+        ]);
+        assert_compiles("fn(xs) { for (x in xs) { } }", vec![function(vec![
+            make(Op::GetLocal, &[0]),
+            make(Op::Iterate, &[]),
+            make(Op::Next, &[12]),
+            make(Op::SetLocal, &[1]),
+            make(Op::Jump, &[3]),
+            make(Op::Pop, &[]),
+            make(Op::Return, &[]),
+        ])], vec![
+            make(Op::Closure, &[0, 0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_break_pops_the_iterator_and_the_loop_variable_goes_out_of_scope() {
+        assert_compiles("let x = 5; for (x in []) { break; } x", vec![Expected::Integer(5)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::Array, &[0]),
+            make(Op::Iterate, &[]),
+            make(Op::Next, &[23]),
+            make(Op::SetGlobal, &[1]),
+            make(Op::Jump, &[22]),
+            make(Op::Jump, &[10]),
+            make(Op::Pop, &[]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_jumps_out_of_expressions_pop_their_operands() {
+        assert_compiles("while (true) { 1 + if (true) { break; } }", vec![Expected::Integer(1)], vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[25]),
+            make(Op::Constant, &[0]),
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[19]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[25]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[20]),
+            make(Op::Null, &[]),
+            make(Op::Add, &[]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[0]),
+        ]);
+        assert_compiles("for (x in []) { [x, if (true) { continue; } else { break; }] }", vec![], vec![
+            make(Op::Array, &[0]),
+            make(Op::Iterate, &[]),
+            make(Op::Next, &[38]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[25]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[4]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[30]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[37]),
+            make(Op::Null, &[]),
+            make(Op::Array, &[2]),
+            make(Op::Pop, &[]),
+            make(Op::Jump, &[4]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[test]
+    fn test_jumps_keep_the_operands_of_expressions_around_the_loop() {
+        assert_compiles("[1, fn() { while (true) { break; } }]; [2, if (true) { while (true) { break; } }]",
+                        vec![Expected::Integer(1), function(vec![
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[10]),
+            make(Op::Jump, &[10]),
+            make(Op::Jump, &[0]),
+            make(Op::Return, &[]),
+        ]), Expected::Integer(2)], vec![
+            make(Op::Constant, &[0]),
+            make(Op::Closure, &[1, 0]),
+            make(Op::Array, &[2]),
+            make(Op::Pop, &[]),
+            make(Op::Constant, &[2]),
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[32]),
+            make(Op::True, &[]),
+            make(Op::JumpNotTruthy, &[28]),
+            make(Op::Jump, &[28]),
+            make(Op::Jump, &[18]),
+            make(Op::Null, &[]),
+            make(Op::Jump, &[33]),
+            make(Op::Null, &[]),
+            make(Op::Array, &[2]),
+            make(Op::Pop, &[]),
+        ]);
+    }
+
+    #[rstest]
+    #[case("x", "identifier not found: x", Span::new(0, 1, 1, 1))]
+    #[case("fn() { 1 + y }", "identifier not found: y", Span::new(11, 12, 1, 12))]
+    #[case("let f = fn() { y = 1 }", "assignment to undeclared variable: y", Span::new(15, 16, 1, 16))]
+    #[case("len = 1", "assignment to undeclared variable: len", Span::new(0, 3, 1, 1))]
+    #[case("let f = fn() { f = 1 }", "cannot assign to the function `f` in its own body", Span::new(15, 16, 1, 16))]
+    #[case("let f = fn() { fn() { f = 1 } }", "cannot assign to the function `f` in its own body", Span::new(22, 23, 1, 23))]
+    #[case("let m = macro(x) { x }; m(1)", "macro literal outside of a top-level let", Span::new(8, 22, 1, 9))]
+    fn test_errors(#[case] input: &str, #[case] message: &str, #[case] span: Span) {
+        assert_eq!(compile_error(input), CompileError::new(String::from(message), span));
+    }
+
+    #[test]
+    fn test_too_many_locals() {
+        let lets: Vec<String> = (0..257).map(|i| format!("let a{} = 0;", i)).collect();
+        let input = format!("fn() {{ {} }}", lets.join(" "));
+
+        let error = compile_error(&input);
+
+        assert_eq!(error.message, "too many local variables (at most 255)");
+        assert_eq!(error.span.start, input.find("a256").unwrap());
+    }
+
+    #[test]
+    fn test_compilers_keep_globals_across_programs() {
+        let mut compiler = Compiler::new();
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        compiler.compile(&Parser::new(MonkeyLexer::new(tokenizer.clone(), "let a = 1;")).parse_program()).unwrap();
+        compiler.compile(&Parser::new(MonkeyLexer::new(tokenizer, "a")).parse_program()).unwrap();
+
+        assert_eq!(compiler.bytecode().instructions.to_string(), disassemble(vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Pop, &[]),
+        ]));
+    }
+
+    #[test]
+    fn test_a_failed_program_leaves_nothing_behind() {
+        let mut compiler = Compiler::new();
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let parse = |input| Parser::new(MonkeyLexer::new(tokenizer.clone(), input)).parse_program();
+        assert!(compiler.compile(&parse("let a = 1; fn() { 1 + y }")).is_err());
+        assert_eq!(compiler.compile(&parse("a")).unwrap_err().message, "identifier not found: a");
+        compiler.compile(&parse("let b = 2; b")).unwrap();
+
+        let bytecode = compiler.bytecode();
+        assert_eq!(bytecode.instructions.to_string(), disassemble(vec![
+            make(Op::Constant, &[0]),
+            make(Op::SetGlobal, &[0]),
+            make(Op::GetGlobal, &[0]),
+            make(Op::Pop, &[]),
+        ]));
+        assert_eq!(bytecode.constants, [Constant::Integer(2)]);
+    }
+}
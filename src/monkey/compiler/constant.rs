@@ -0,0 +1,35 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::monkey::compiler::Instructions;
+use crate::symbol::Symbol;
+
+/// A value in the constant pool of compiled code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(isize),
+    String(Rc<str>),
+    Function(Rc<CompiledFunction>),
+}
+
+/// The bytecode of a function literal, and what a call needs to know to run it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub name: Option<Symbol>,
+    pub instructions: Instructions,
+    /// The number of slots for local variables, parameters included.
+    pub locals: usize,
+    pub parameters: usize,
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "{:?}", value),
+            Constant::Function(function) => match function.name {
+                Some(name) => write!(f, "compiled function {}", name),
+                None => write!(f, "compiled function <anonymous>"),
+            },
+        }
+    }
+}
@@ -0,0 +1,12 @@
+mod code;
+mod constant;
+mod symbol_table;
+mod compile_error;
+#[allow(clippy::module_inception)]
+mod compiler;
+
+pub use self::code::{make, max_operand, read_operands, Definition, Instructions, Opcode};
+pub use self::constant::{CompiledFunction, Constant};
+pub use self::symbol_table::{Binding, Scope, SymbolTable};
+pub use self::compile_error::CompileError;
+pub use self::compiler::{Bytecode, Compiler};
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+use crate::symbol::Symbol;
+
+/// Where the value of a name is kept at run time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    /// A parameter or variable of the function being compiled.
+    Local,
+    /// A local variable whose slot holds a cell, which closures share so that they see its
+    /// assignments.
+    Cell,
+    Builtin,
+    /// A variable of an enclosing function, whose value the closure captured.
+    Free,
+    /// A variable of an enclosing function kept in a cell, which the closure captured.
+    FreeCell,
+    /// The name of the function being compiled, which is the running closure itself.
+    Function,
+}
+
+/// What a name resolves to: a scope, and a slot within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub name: Symbol,
+    pub scope: Scope,
+    pub index: usize,
+}
+
+/// The names visible in the function being compiled, or at the top level when it has no outer
+/// table.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<Symbol, Binding>,
+    definitions: usize,
+    /// The bindings, in the outer table, of the variables this function captures. The index
+    /// of each is its `Free` slot.
+    free: Vec<Binding>,
+    /// The names of the variables to keep in cells.
+    cells: HashSet<Symbol>,
+}
+
+impl Binding {
+    pub fn new(name: Symbol, scope: Scope, index: usize) -> Binding {
+        Binding { name, scope, index }
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// A table for a function defined in the scope of `outer`.
+    pub fn enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable { outer: Some(Box::new(outer)), ..SymbolTable::default() }
+    }
+
+    /// Keeps the local variables named in `cells` in cells.
+    pub fn with_cells(self, cells: HashSet<Symbol>) -> SymbolTable {
+        SymbolTable { cells, ..self }
+    }
+
+    /// Gives back the table this one was enclosed in.
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    /// The number of slots the variables defined in this table need.
+    pub fn definitions(&self) -> usize {
+        self.definitions
+    }
+
+    pub fn free(&self) -> &[Binding] {
+        &self.free
+    }
+
+    fn variable_scope(&self, name: Symbol) -> Scope {
+        match self.outer {
+            None => Scope::Global,
+            Some(_) if self.cells.contains(&name) => Scope::Cell,
+            Some(_) => Scope::Local,
+        }
+    }
+
+    /// Binds a variable in this table, reusing its slot if it is already defined here, as a
+    /// second `let` of a name only replaces its value.
+    pub fn define(&mut self, name: Symbol) -> Binding {
+        match self.store.get(&name) {
+            Some(binding) if binding.scope == self.variable_scope(name) => *binding,
+            _ => self.shadow(name).0
+        }
+    }
+
+    /// Binds a variable in a new slot, returning the binding it hides in this table so that
+    /// `restore` can bring it back.
+    pub fn shadow(&mut self, name: Symbol) -> (Binding, Option<Binding>) {
+        let binding = Binding::new(name, self.variable_scope(name), self.definitions);
+        self.definitions += 1;
+        (binding, self.store.insert(name, binding))
+    }
+
+    /// Undoes a `shadow`, without freeing the slot it took.
+    pub fn restore(&mut self, name: Symbol, hidden: Option<Binding>) {
+        match hidden {
+            Some(binding) => self.store.insert(name, binding),
+            None => self.store.remove(&name)
+        };
+    }
+
+    pub fn define_builtin(&mut self, index: usize, name: Symbol) -> Binding {
+        let binding = Binding::new(name, Scope::Builtin, index);
+        self.store.insert(name, binding);
+        binding
+    }
+
+    /// Binds the name a function is defined under, in the table of its own body.
+    pub fn define_function_name(&mut self, name: Symbol) -> Binding {
+        let binding = Binding::new(name, Scope::Function, 0);
+        self.store.insert(name, binding);
+        binding
+    }
+
+    /// Looks a name up here, then in the enclosing tables. A local variable of an enclosing
+    /// function becomes a free variable of this one, and of every function in between.
+    pub fn resolve(&mut self, name: Symbol) -> Option<Binding> {
+        if let Some(binding) = self.store.get(&name) {
+            return Some(*binding);
+        }
+        let binding = self.outer.as_mut()?.resolve(name)?;
+        match binding.scope {
+            Scope::Global | Scope::Builtin => Some(binding),
+            Scope::Local | Scope::Free | Scope::Function => Some(self.define_free(binding, Scope::Free)),
+            Scope::Cell | Scope::FreeCell => Some(self.define_free(binding, Scope::FreeCell)),
+        }
+    }
+
+    fn define_free(&mut self, original: Binding, scope: Scope) -> Binding {
+        let binding = Binding::new(original.name, scope, self.free.len());
+        self.free.push(original);
+        self.store.insert(original.name, binding);
+        binding
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::monkey::compiler::{Binding, Scope, SymbolTable};
+    use crate::symbol::Symbol;
+
+    fn binding(name: &str, scope: Scope, index: usize) -> Option<Binding> {
+        Some(Binding::new(Symbol::intern(name), scope, index))
+    }
+
+    #[test]
+    fn test_define() {
+        let mut global = SymbolTable::new();
+        assert_eq!(Some(global.define(Symbol::intern("a"))), binding("a", Scope::Global, 0));
+        assert_eq!(Some(global.define(Symbol::intern("b"))), binding("b", Scope::Global, 1));
+
+        let mut local = SymbolTable::enclosed(global);
+        assert_eq!(Some(local.define(Symbol::intern("c"))), binding("c", Scope::Local, 0));
+        assert_eq!(Some(local.define(Symbol::intern("a"))), binding("a", Scope::Local, 1));
+    }
+
+    #[test]
+    fn test_define_reuses_the_slot_of_a_name() {
+        let mut global = SymbolTable::new();
+        global.define(Symbol::intern("a"));
+        global.define(Symbol::intern("b"));
+
+        assert_eq!(Some(global.define(Symbol::intern("a"))), binding("a", Scope::Global, 0));
+        assert_eq!(global.definitions(), 2);
+    }
+
+    #[test]
+    fn test_resolve_local() {
+        let mut global = SymbolTable::new();
+        global.define(Symbol::intern("a"));
+        global.define(Symbol::intern("b"));
+        let mut first = SymbolTable::enclosed(global);
+        first.define(Symbol::intern("c"));
+        let mut second = SymbolTable::enclosed(first);
+        second.define(Symbol::intern("e"));
+
+        assert_eq!(second.resolve(Symbol::intern("a")), binding("a", Scope::Global, 0));
+        assert_eq!(second.resolve(Symbol::intern("b")), binding("b", Scope::Global, 1));
+        assert_eq!(second.resolve(Symbol::intern("e")), binding("e", Scope::Local, 0));
+        assert_eq!(second.resolve(Symbol::intern("f")), None);
+    }
+
+    #[test]
+    fn test_resolve_builtins_from_any_depth() {
+        let mut global = SymbolTable::new();
+        global.define_builtin(0, Symbol::intern("len"));
+        global.define_builtin(1, Symbol::intern("puts"));
+        let mut nested = SymbolTable::enclosed(SymbolTable::enclosed(global));
+
+        assert_eq!(nested.resolve(Symbol::intern("len")), binding("len", Scope::Builtin, 0));
+        assert_eq!(nested.resolve(Symbol::intern("puts")), binding("puts", Scope::Builtin, 1));
+        assert!(nested.free().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define(Symbol::intern("a"));
+        let mut first = SymbolTable::enclosed(global);
+        first.define(Symbol::intern("c"));
+        first.define(Symbol::intern("d"));
+        let mut second = SymbolTable::enclosed(first);
+        second.define(Symbol::intern("e"));
+
+        assert_eq!(second.resolve(Symbol::intern("a")), binding("a", Scope::Global, 0));
+        assert_eq!(second.resolve(Symbol::intern("d")), binding("d", Scope::Free, 0));
+        assert_eq!(second.resolve(Symbol::intern("c")), binding("c", Scope::Free, 1));
+        assert_eq!(second.resolve(Symbol::intern("e")), binding("e", Scope::Local, 0));
+        assert_eq!(second.resolve(Symbol::intern("d")), binding("d", Scope::Free, 0));
+        assert_eq!(second.free(), [binding("d", Scope::Local, 1).unwrap(), binding("c", Scope::Local, 0).unwrap()]);
+    }
+
+    #[test]
+    fn test_free_variables_pass_through_every_function_in_between() {
+        let mut first = SymbolTable::enclosed(SymbolTable::new());
+        first.define(Symbol::intern("a"));
+        let mut third = SymbolTable::enclosed(SymbolTable::enclosed(first));
+
+        assert_eq!(third.resolve(Symbol::intern("a")), binding("a", Scope::Free, 0));
+        assert_eq!(third.free(), [binding("a", Scope::Free, 0).unwrap()]);
+        let second = third.into_outer().unwrap();
+        assert_eq!(second.free(), [binding("a", Scope::Local, 0).unwrap()]);
+    }
+
+    #[test]
+    fn test_cells() {
+        let cells = [Symbol::intern("a")].into_iter().collect();
+        let mut first = SymbolTable::enclosed(SymbolTable::new()).with_cells(cells);
+        assert_eq!(Some(first.define(Symbol::intern("a"))), binding("a", Scope::Cell, 0));
+        assert_eq!(Some(first.define(Symbol::intern("b"))), binding("b", Scope::Local, 1));
+        assert_eq!(Some(first.define(Symbol::intern("a"))), binding("a", Scope::Cell, 0));
+        let mut third = SymbolTable::enclosed(SymbolTable::enclosed(first));
+
+        assert_eq!(third.resolve(Symbol::intern("a")), binding("a", Scope::FreeCell, 0));
+        assert_eq!(third.resolve(Symbol::intern("b")), binding("b", Scope::Free, 1));
+        let second = third.into_outer().unwrap();
+        assert_eq!(second.free(), [binding("a", Scope::Cell, 0).unwrap(), binding("b", Scope::Local, 1).unwrap()]);
+    }
+
+    #[test]
+    fn test_function_name() {
+        let mut global = SymbolTable::new();
+        global.define_function_name(Symbol::intern("f"));
+
+        assert_eq!(global.resolve(Symbol::intern("f")), binding("f", Scope::Function, 0));
+    }
+
+    #[test]
+    fn test_variables_shadow_the_function_name() {
+        let mut local = SymbolTable::enclosed(SymbolTable::new());
+        local.define_function_name(Symbol::intern("f"));
+        local.define(Symbol::intern("f"));
+
+        assert_eq!(local.resolve(Symbol::intern("f")), binding("f", Scope::Local, 0));
+    }
+
+    #[test]
+    fn test_shadow_and_restore() {
+        let mut global = SymbolTable::new();
+        global.define(Symbol::intern("x"));
+
+        let (inner, hidden) = global.shadow(Symbol::intern("x"));
+        assert_eq!(Some(inner), binding("x", Scope::Global, 1));
+        assert_eq!(global.resolve(Symbol::intern("x")), binding("x", Scope::Global, 1));
+
+        global.restore(Symbol::intern("x"), hidden);
+        assert_eq!(global.resolve(Symbol::intern("x")), binding("x", Scope::Global, 0));
+        assert_eq!(global.definitions(), 2);
+
+        let (_, hidden) = global.shadow(Symbol::intern("y"));
+        global.restore(Symbol::intern("y"), hidden);
+        assert_eq!(global.resolve(Symbol::intern("y")), None);
+    }
+}
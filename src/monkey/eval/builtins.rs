@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::monkey::eval::Object;
+use crate::symbol::Symbol;
+
+/// The Rust side of a built-in function. It gets arguments whose number has already been
+/// checked, and fails with the message of the runtime error to raise.
+pub type BuiltinFunction = dyn Fn(&[Object]) -> Result<Object, String>;
+
+/// A function implemented in Rust and callable from Monkey.
+pub struct Builtin {
+    pub name: Symbol,
+    /// The number of arguments it takes, or `None` when it takes any number.
+    pub arity: Option<usize>,
+    function: Rc<BuiltinFunction>,
+}
+
+/// The built-in functions an evaluator can see, looked up after the program's own bindings.
+#[derive(Default)]
+pub struct Builtins {
+    functions: HashMap<Symbol, Rc<Builtin>>,
+}
+
+impl Builtin {
+    pub fn new<F>(name: &str, arity: Option<usize>, function: F) -> Builtin
+    where F: Fn(&[Object]) -> Result<Object, String> + 'static {
+        Builtin { name: Symbol::intern(name), arity, function: Rc::new(function) }
+    }
+
+    pub fn call(&self, arguments: &[Object]) -> Result<Object, String> {
+        (self.function)(arguments)
+    }
+}
+
+impl Builtins {
+    /// A registry without any functions.
+    pub fn new() -> Builtins {
+        Builtins::default()
+    }
+
+    /// `len`, `first`, `last`, `rest`, `push` and `puts`.
+    pub fn standard() -> Builtins {
+        Builtins::new()
+            .with("len", Some(1), len)
+            .with("first", Some(1), first)
+            .with("last", Some(1), last)
+            .with("rest", Some(1), rest)
+            .with("push", Some(2), push)
+            .with("puts", None, puts)
+    }
+
+    pub fn with<F>(mut self, name: &str, arity: Option<usize>, function: F) -> Builtins
+    where F: Fn(&[Object]) -> Result<Object, String> + 'static {
+        self.register(name, arity, function);
+        self
+    }
+
+    /// Adds a function, replacing any built-in of the same name.
+    pub fn register<F>(&mut self, name: &str, arity: Option<usize>, function: F)
+    where F: Fn(&[Object]) -> Result<Object, String> + 'static {
+        let builtin = Builtin::new(name, arity, function);
+        self.functions.insert(builtin.name, Rc::new(builtin));
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Object> {
+        self.functions.get(&name).map(|builtin| Object::Builtin(builtin.clone()))
+    }
+
+    /// The names of the functions, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.functions.keys().copied()
+    }
+}
+
+fn type_error(function: &str, expected: &str, argument: &Object) -> String {
+    format!("argument to `{}` must be {}, got {}", function, expected, argument.type_name())
+}
+
+fn len(arguments: &[Object]) -> Result<Object, String> {
+    match &arguments[0] {
+        Object::String(value) => Ok(Object::Integer(value.chars().count() as isize)),
+        Object::Array(elements) => Ok(Object::Integer(elements.len() as isize)),
+        argument => Err(type_error("len", "STRING or ARRAY", argument))
+    }
+}
+
+fn first(arguments: &[Object]) -> Result<Object, String> {
+    match &arguments[0] {
+        Object::Array(elements) => Ok(elements.first().cloned().unwrap_or(Object::Null)),
+        argument => Err(type_error("first", "ARRAY", argument))
+    }
+}
+
+fn last(arguments: &[Object]) -> Result<Object, String> {
+    match &arguments[0] {
+        Object::Array(elements) => Ok(elements.last().cloned().unwrap_or(Object::Null)),
+        argument => Err(type_error("last", "ARRAY", argument))
+    }
+}
+
+/// Every element but the first, or `null` for an empty array.
+fn rest(arguments: &[Object]) -> Result<Object, String> {
+    match &arguments[0] {
+        Object::Array(elements) if elements.is_empty() => Ok(Object::Null),
+        Object::Array(elements) => Ok(Object::Array(Rc::new(elements[1..].to_vec()))),
+        argument => Err(type_error("rest", "ARRAY", argument))
+    }
+}
+
+/// A new array with the element added at the end, leaving the argument unchanged.
+fn push(arguments: &[Object]) -> Result<Object, String> {
+    match &arguments[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.as_ref().clone();
+            elements.push(arguments[1].clone());
+            Ok(Object::Array(Rc::new(elements)))
+        }
+        argument => Err(type_error("push", "ARRAY", argument))
+    }
+}
+
+/// Prints each argument on its own line.
+fn puts(arguments: &[Object]) -> Result<Object, String> {
+    for argument in arguments {
+        println!("{}", argument);
+    }
+    Ok(Object::Null)
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Builtin) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for Builtins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::monkey::eval::Object;
+use crate::symbol::Symbol;
+
+/// The bindings of one scope. Lookups that miss fall through to the enclosing scope.
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<Symbol, Object>,
+    outer: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    /// A new scope inside `outer`, such as the scope of a function call.
+    pub fn enclosed(outer: Rc<RefCell<Environment>>) -> Environment {
+        Environment { store: HashMap::new(), outer: Some(outer) }
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Object> {
+        match self.store.get(&name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.borrow().get(name))
+        }
+    }
+
+    /// Binds `name` in this scope, shadowing any binding of it in enclosing scopes.
+    pub fn set(&mut self, name: Symbol, value: Object) {
+        self.store.insert(name, value);
+    }
+
+    /// Rebinds `name` in the innermost scope that has it. Returns false, changing nothing,
+    /// when no scope has it.
+    pub fn assign(&mut self, name: Symbol, value: Object) -> bool {
+        if let Some(binding) = self.store.get_mut(&name) {
+            *binding = value;
+            true
+        } else {
+            self.outer.as_ref().is_some_and(|outer| outer.borrow_mut().assign(name, value))
+        }
+    }
+}
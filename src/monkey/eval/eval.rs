@@ -0,0 +1,862 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::LazyLock;
+use crate::lexer::Span;
+use crate::monkey::ast::{BlockStatement, Expression, ExpressionKind, Identifier, InfixOperator, PrefixOperator, Program, Statement};
+use crate::monkey::eval::{Builtin, Builtins, Environment, Frame, Function, Object, RuntimeError};
+use crate::symbol::Symbol;
+
+/// Evaluates to the object, or returns it from the enclosing function if it is an error or
+/// otherwise cuts evaluation short.
+macro_rules! try_eval {
+    ($object:expr) => {
+        match $object {
+            object if object.is_abrupt() => return object,
+            object => object,
+        }
+    };
+}
+
+/// The name of the `quote` form, which calls evaluate specially.
+static QUOTE: LazyLock<Symbol> = LazyLock::new(|| Symbol::intern("quote"));
+
+/// How many calls can be active at once, by default, before evaluation stops with a stack
+/// overflow error rather than overflowing the evaluator's own stack. It fits the 8 MiB stack
+/// of a main thread, even in debug builds.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
+/// The native stack a Monkey call may take, which covers the several nested Rust calls of a
+/// debug build with room to spare.
+const STACK_PER_CALL: usize = 24 << 10;
+
+/// The native stack evaluation needs besides its calls.
+const BASE_STACK_SIZE: usize = 1 << 20;
+
+/// The native stack size that lets an evaluator with `max_call_depth` fail with a stack
+/// overflow error before it runs out of stack.
+pub fn stack_size(max_call_depth: usize) -> usize {
+    BASE_STACK_SIZE + max_call_depth * STACK_PER_CALL
+}
+
+/// A tree-walking evaluator for Monkey programs.
+#[derive(Debug)]
+pub struct Evaluator {
+    builtins: Builtins,
+    pub(super) call_stack: Vec<Frame>,
+    max_call_depth: usize,
+    /// Whether `quote` renames the variables its code binds, which it does while a macro
+    /// body runs during expansion.
+    pub(super) hygienic_quotes: bool,
+    /// How many fresh names hygienic quotes have made.
+    pub(super) gensym_count: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator::with_builtins(Builtins::standard())
+    }
+}
+
+impl Evaluator {
+    /// An evaluator with the standard built-in functions.
+    pub fn new() -> Evaluator {
+        Evaluator::default()
+    }
+
+    pub fn with_builtins(builtins: Builtins) -> Evaluator {
+        Evaluator {
+            builtins,
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            hygienic_quotes: false,
+            gensym_count: 0,
+        }
+    }
+
+    /// Sets how many calls can be active at once. Beyond the default, evaluation needs a
+    /// thread with `stack_size(depth)` bytes of stack.
+    pub fn with_max_call_depth(mut self, depth: usize) -> Evaluator {
+        self.max_call_depth = depth;
+        self
+    }
+
+    pub fn builtins_mut(&mut self) -> &mut Builtins {
+        &mut self.builtins
+    }
+
+    /// Runs the program in `environment` and returns the value of its last statement, the
+    /// value it returned, or the error that stopped it.
+    pub fn eval_program(&mut self, program: &Program, environment: &Rc<RefCell<Environment>>) -> Object {
+        let mut result = Object::Null;
+        for statement in &program.statements {
+            match self.eval_statement(statement, environment) {
+                Object::ReturnValue(value) => return *value,
+                error @ Object::Error(_) => return error,
+                value => result = value
+            }
+        }
+        result
+    }
+
+    /// Like `eval_program`, but leaves return values wrapped so they end the enclosing function,
+    /// and stops at `break` and `continue` for the enclosing loop to handle.
+    pub(super) fn eval_block(&mut self, block: &BlockStatement, environment: &Rc<RefCell<Environment>>) -> Object {
+        let mut result = Object::Null;
+        for statement in &block.statements {
+            result = self.eval_statement(statement, environment);
+            if matches!(result, Object::ReturnValue(_) | Object::Error(_) | Object::Break | Object::Continue) {
+                return result;
+            }
+        }
+        result
+    }
+
+    /// Runs one iteration of a loop body. Returns the object that ends the loop, if any: a
+    /// return value, an error, or `Null` for a `break`.
+    fn eval_loop_body(&mut self, body: &BlockStatement, environment: &Rc<RefCell<Environment>>) -> Option<Object> {
+        match self.eval_block(body, environment) {
+            Object::Break => Some(Object::Null),
+            result @ (Object::ReturnValue(_) | Object::Error(_)) => Some(result),
+            _ => None
+        }
+    }
+
+    /// An error object for a failure at `span`, traced through the calls active now.
+    pub(super) fn error(&self, message: String, span: Span) -> Object {
+        let trace = self.call_stack.iter().rev().cloned().collect();
+        Object::Error(Rc::new(RuntimeError::new(message, span, trace)))
+    }
+
+    fn or_error(&self, result: Result<Object, String>, span: Span) -> Object {
+        result.unwrap_or_else(|message| self.error(message, span))
+    }
+
+    fn eval_statement(&mut self, statement: &Statement, environment: &Rc<RefCell<Environment>>) -> Object {
+        match statement {
+            Statement::Let { name, value } => {
+                let value = match &value.kind {
+                    ExpressionKind::Function { parameters, body } => {
+                        Self::eval_function_literal(Some(name.name), parameters, body, environment)
+                    }
+                    _ => try_eval!(self.eval_expression(value, environment))
+                };
+                environment.borrow_mut().set(name.name, value);
+                Object::Null
+            }
+            Statement::Assign { name, value } => {
+                let value = try_eval!(self.eval_expression(value, environment));
+                if environment.borrow_mut().assign(name.name, value) {
+                    Object::Null
+                } else {
+                    self.error(format!("assignment to undeclared variable: {}", name.name), name.span)
+                }
+            }
+            Statement::Return(value) => {
+                let value = try_eval!(self.eval_expression(value, environment));
+                Object::ReturnValue(Box::new(value))
+            }
+            Statement::Expression(expression) => self.eval_expression(expression, environment),
+            Statement::While { condition, body } => loop {
+                let condition = try_eval!(self.eval_expression(condition, environment));
+                if !condition.is_truthy() {
+                    return Object::Null;
+                }
+                if let Some(result) = self.eval_loop_body(body, environment) {
+                    return result;
+                }
+            },
+            Statement::For { variable, iterable, body } => {
+                let iterable_span = iterable.span;
+                let elements: Vec<Object> = match try_eval!(self.eval_expression(iterable, environment)) {
+                    Object::Array(elements) => elements.as_ref().clone(),
+                    Object::String(value) => value.chars().map(|ch| Object::String(Rc::from(ch.to_string()))).collect(),
+                    iterable => return self.error(format!("cannot iterate over {}", iterable.type_name()), iterable_span)
+                };
+                for element in elements {
+                    let mut scope = Environment::enclosed(environment.clone());
+                    scope.set(variable.name, element);
+                    if let Some(result) = self.eval_loop_body(body, &Rc::new(RefCell::new(scope))) {
+                        return result;
+                    }
+                }
+                Object::Null
+            }
+            Statement::Break => Object::Break,
+            Statement::Continue => Object::Continue,
+        }
+    }
+
+    pub(super) fn eval_expression(&mut self, expression: &Expression, environment: &Rc<RefCell<Environment>>) -> Object {
+        match &expression.kind {
+            ExpressionKind::Identifier(name) => {
+                let value = environment.borrow().get(*name).or_else(|| self.builtins.get(*name));
+                match value {
+                    Some(value) => value,
+                    None => self.error(format!("identifier not found: {}", name), expression.span)
+                }
+            }
+            ExpressionKind::Integer(value) => Object::Integer(*value),
+            ExpressionKind::Boolean(value) => Object::Boolean(*value),
+            ExpressionKind::String(value) => Object::String(Rc::from(value.as_str())),
+            ExpressionKind::Prefix { operator, right } => {
+                let right = try_eval!(self.eval_expression(right, environment));
+                self.or_error(Self::eval_prefix(*operator, right), expression.span)
+            }
+            ExpressionKind::Infix { operator: operator @ (InfixOperator::And | InfixOperator::Or), left, right } => {
+                let left = try_eval!(self.eval_expression(left, environment));
+                if left.is_truthy() == (*operator == InfixOperator::Or) {
+                    return Object::Boolean(left.is_truthy());
+                }
+                let right = try_eval!(self.eval_expression(right, environment));
+                Object::Boolean(right.is_truthy())
+            }
+            ExpressionKind::Infix { operator, left, right } => {
+                let left = try_eval!(self.eval_expression(left, environment));
+                let right = try_eval!(self.eval_expression(right, environment));
+                self.or_error(Self::eval_infix(*operator, left, right), expression.span)
+            }
+            ExpressionKind::If { condition, consequence, alternative } => {
+                let condition = try_eval!(self.eval_expression(condition, environment));
+                if condition.is_truthy() {
+                    self.eval_block(consequence, environment)
+                } else if let Some(alternative) = alternative {
+                    self.eval_block(alternative, environment)
+                } else {
+                    Object::Null
+                }
+            }
+            ExpressionKind::Function { parameters, body } => {
+                Self::eval_function_literal(None, parameters, body, environment)
+            }
+            ExpressionKind::Macro { .. } => {
+                self.error(String::from("macro literal outside of a top-level let"), expression.span)
+            }
+            ExpressionKind::Call { function, arguments } if function.kind == ExpressionKind::Identifier(*QUOTE) => {
+                match &arguments[..] {
+                    [argument] => self.eval_quote(argument, environment),
+                    _ => self.error(format!("wrong number of arguments to `quote`: expected 1, got {}", arguments.len()), expression.span)
+                }
+            }
+            ExpressionKind::Call { function, arguments } => {
+                let function = try_eval!(self.eval_expression(function, environment));
+                let arguments = match self.eval_expressions(arguments, environment) {
+                    Ok(arguments) => arguments,
+                    Err(abrupt) => return abrupt
+                };
+                self.apply_function(function, arguments, expression.span)
+            }
+            ExpressionKind::Array(elements) => match self.eval_expressions(elements, environment) {
+                Ok(elements) => Object::Array(Rc::new(elements)),
+                Err(abrupt) => abrupt
+            },
+            ExpressionKind::Hash(pairs) => {
+                let mut hash = BTreeMap::new();
+                for (key_expression, value) in pairs {
+                    let key = try_eval!(self.eval_expression(key_expression, environment));
+                    let Some(key) = key.to_hash_key() else {
+                        return self.error(format!("unusable as hash key: {}", key.type_name()), key_expression.span);
+                    };
+                    let value = try_eval!(self.eval_expression(value, environment));
+                    hash.insert(key, value);
+                }
+                Object::Hash(Rc::new(hash))
+            }
+            ExpressionKind::Index { left, index } => {
+                let left = try_eval!(self.eval_expression(left, environment));
+                let index = try_eval!(self.eval_expression(index, environment));
+                self.or_error(Self::eval_index(left, index), expression.span)
+            }
+        }
+    }
+
+    /// Evaluates the expressions in order, stopping at the first error, `return`, `break` or
+    /// `continue`.
+    fn eval_expressions(&mut self, expressions: &[Expression], environment: &Rc<RefCell<Environment>>) -> Result<Vec<Object>, Object> {
+        let mut values = Vec::with_capacity(expressions.len());
+        for expression in expressions {
+            match self.eval_expression(expression, environment) {
+                abrupt if abrupt.is_abrupt() => return Err(abrupt),
+                value => values.push(value)
+            }
+        }
+        Ok(values)
+    }
+
+    fn eval_function_literal(name: Option<Symbol>, parameters: &[Identifier], body: &BlockStatement, environment: &Rc<RefCell<Environment>>) -> Object {
+        Object::Function(Rc::new(Function {
+            name,
+            parameters: parameters.to_vec(),
+            body: body.clone(),
+            environment: environment.clone(),
+        }))
+    }
+
+    fn eval_prefix(operator: PrefixOperator, right: Object) -> Result<Object, String> {
+        match (operator, right) {
+            (PrefixOperator::Not, right) => Ok(Object::Boolean(!right.is_truthy())),
+            (PrefixOperator::Negate, Object::Integer(value)) => match value.checked_neg() {
+                Some(value) => Ok(Object::Integer(value)),
+                None => Err(String::from("integer overflow"))
+            },
+            (operator, right) => Err(format!("unknown operator: {}{}", operator, right.type_name()))
+        }
+    }
+
+    fn eval_infix(operator: InfixOperator, left: Object, right: Object) -> Result<Object, String> {
+        match (&left, &right) {
+            (Object::Integer(left), Object::Integer(right)) => Self::eval_integer_infix(operator, *left, *right),
+            (Object::String(left), Object::String(right)) if operator == InfixOperator::Add => {
+                Ok(Object::String(Rc::from(format!("{}{}", left, right))))
+            }
+            _ if operator == InfixOperator::Equal => Ok(Object::Boolean(left == right)),
+            _ if operator == InfixOperator::NotEqual => Ok(Object::Boolean(left != right)),
+            _ if left.type_name() != right.type_name() => {
+                Err(format!("type mismatch: {} {} {}", left.type_name(), operator, right.type_name()))
+            }
+            _ => Err(format!("unknown operator: {} {} {}", left.type_name(), operator, right.type_name()))
+        }
+    }
+
+    fn eval_integer_infix(operator: InfixOperator, left: isize, right: isize) -> Result<Object, String> {
+        let value = match operator {
+            InfixOperator::Add => left.checked_add(right),
+            InfixOperator::Subtract => left.checked_sub(right),
+            InfixOperator::Multiply => left.checked_mul(right),
+            InfixOperator::Divide if right == 0 => return Err(String::from("division by zero")),
+            InfixOperator::Divide => left.checked_div(right),
+            InfixOperator::Modulo if right == 0 => return Err(String::from("modulo by zero")),
+            InfixOperator::Modulo => left.checked_rem(right),
+            InfixOperator::LessThan => return Ok(Object::Boolean(left < right)),
+            InfixOperator::GreaterThan => return Ok(Object::Boolean(left > right)),
+            InfixOperator::LessEqual => return Ok(Object::Boolean(left <= right)),
+            InfixOperator::GreaterEqual => return Ok(Object::Boolean(left >= right)),
+            InfixOperator::Equal => return Ok(Object::Boolean(left == right)),
+            InfixOperator::NotEqual => return Ok(Object::Boolean(left != right)),
+            InfixOperator::And | InfixOperator::Or => unreachable!("logical operators short-circuit before evaluating both operands"),
+        };
+        value.map(Object::Integer).ok_or_else(|| String::from("integer overflow"))
+    }
+
+    fn eval_index(left: Object, index: Object) -> Result<Object, String> {
+        match (&left, &index) {
+            (Object::Array(elements), Object::Integer(i)) => {
+                match usize::try_from(*i).ok().and_then(|i| elements.get(i)) {
+                    Some(element) => Ok(element.clone()),
+                    None => Err(format!("index out of range: {} for an array of length {}", i, elements.len()))
+                }
+            }
+            (Object::Hash(pairs), _) => match index.to_hash_key() {
+                Some(key) => match pairs.get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(format!("key not found: {:?}", Object::from(key).to_string()))
+                },
+                None => Err(format!("unusable as hash key: {}", index.type_name()))
+            },
+            _ => Err(format!("index operator not supported: {}[{}]", left.type_name(), index.type_name()))
+        }
+    }
+
+    /// Calls `function` for the call expression at `span`, with a frame on the call stack
+    /// while its body runs.
+    fn apply_function(&mut self, function: Object, arguments: Vec<Object>, span: Span) -> Object {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => return self.apply_builtin(&builtin, &arguments, span),
+            _ => return self.error(format!("not a function: {}", function.type_name()), span)
+        };
+        if arguments.len() != function.parameters.len() {
+            return self.error(format!("wrong number of arguments: expected {}, got {}",
+                                      function.parameters.len(), arguments.len()), span);
+        }
+        if self.call_stack.len() >= self.max_call_depth {
+            return self.error(format!("stack overflow: more than {} nested calls", self.max_call_depth), span);
+        }
+        let mut scope = Environment::enclosed(function.environment.clone());
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            scope.set(parameter.name, argument);
+        }
+        self.call_stack.push(Frame::new(function.name, span));
+        let result = self.eval_block(&function.body, &Rc::new(RefCell::new(scope)));
+        self.call_stack.pop();
+        match result {
+            Object::ReturnValue(value) => *value,
+            value => value
+        }
+    }
+
+    fn apply_builtin(&self, builtin: &Builtin, arguments: &[Object], span: Span) -> Object {
+        match builtin.arity {
+            Some(arity) if arguments.len() != arity => self.error(
+                format!("wrong number of arguments to `{}`: expected {}, got {}", builtin.name, arity, arguments.len()),
+                span),
+            _ => self.or_error(builtin.call(arguments), span)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::thread;
+    use rstest::rstest;
+    use crate::lexer::Span;
+    use crate::monkey::eval::{stack_size, Builtins, Environment, Evaluator, Frame, Object, RuntimeError, DEFAULT_MAX_CALL_DEPTH};
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::symbol::Symbol;
+    use crate::token::Tokenize;
+
+    fn eval(input: &str) -> Object {
+        eval_with(Evaluator::new(), input)
+    }
+
+    fn eval_with(mut evaluator: Evaluator, input: &str) -> Object {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut parser = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "unexpected parse errors: {:?}", parser.errors());
+
+        evaluator.eval_program(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    /// Runs `f` on a thread with the stack that `max_call_depth` nested calls need, as test
+    /// threads have less than a main thread.
+    fn with_stack_for<F, T>(max_call_depth: usize, f: F) -> T
+    where F: FnOnce() -> T + Send, T: Send {
+        thread::scope(|scope| {
+            thread::Builder::new().stack_size(stack_size(max_call_depth)).spawn_scoped(scope, f).unwrap().join().unwrap()
+        })
+    }
+
+    fn eval_error(input: &str) -> RuntimeError {
+        match eval(input) {
+            Object::Error(error) => error.as_ref().clone(),
+            object => panic!("expected an error, got {:?}", object)
+        }
+    }
+
+    #[rstest]
+    #[case("5", 5)]
+    #[case("-10", -10)]
+    #[case("5 + 5 + 5 + 5 - 10", 10)]
+    #[case("2 * 2 * 2 * 2 * 2", 32)]
+    #[case("-50 + 100 + -50", 0)]
+    #[case("20 + 2 * -10", 0)]
+    #[case("50 / 2 * 2 + 10", 60)]
+    #[case("3 * (3 * 3) + 10", 37)]
+    #[case("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50)]
+    #[case("10 % 3", 1)]
+    #[case("-10 % 3", -1)]
+    #[case("2 + 7 % 4 * 2", 8)]
+    fn test_integer_expressions(#[case] input: &str, #[case] expected: isize) {
+        assert_eq!(eval(input), Object::Integer(expected));
+    }
+
+    #[rstest]
+    #[case("true", true)]
+    #[case("1 < 2", true)]
+    #[case("1 > 2", false)]
+    #[case("1 == 1", true)]
+    #[case("1 != 1", false)]
+    #[case("true == true", true)]
+    #[case("true != false", true)]
+    #[case("(1 < 2) == true", true)]
+    #[case("(1 > 2) == true", false)]
+    #[case("!true", false)]
+    #[case("!5", false)]
+    #[case("!!5", true)]
+    #[case("\"a\" == \"a\"", true)]
+    #[case("\"a\" != \"b\"", true)]
+    #[case("[1, [2]] == [1, [2]]", true)]
+    #[case("1 == true", false)]
+    #[case("1 <= 1", true)]
+    #[case("2 <= 1", false)]
+    #[case("1 >= 2", false)]
+    #[case("2 >= 2", true)]
+    #[case("true && true", true)]
+    #[case("true && false", false)]
+    #[case("false || true", true)]
+    #[case("false || false", false)]
+    #[case("1 && \"\"", true)]
+    #[case("if (false) { 1 } || 0", true)]
+    #[case("1 < 2 && 2 <= 2 || 1 / 1 == 0", true)]
+    fn test_boolean_expressions(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(eval(input), Object::Boolean(expected));
+    }
+
+    #[rstest]
+    #[case("if (true) { 10 }", Object::Integer(10))]
+    #[case("if (false) { 10 }", Object::Null)]
+    #[case("if (1) { 10 }", Object::Integer(10))]
+    #[case("if (1 > 2) { 10 } else { 20 }", Object::Integer(20))]
+    #[case("if (if (false) { 1 }) { 10 } else { 20 }", Object::Integer(20))]
+    fn test_if_else_expressions(#[case] input: &str, #[case] expected: Object) {
+        assert_eq!(eval(input), expected);
+    }
+
+    #[rstest]
+    #[case("return 10; 9;", 10)]
+    #[case("9; return 2 * 5; 9;", 10)]
+    #[case("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10)]
+    #[case("let f = fn(x) { return x; x + 10; }; f(10);", 10)]
+    #[case("let f = fn(x) { if (x) { return 1; } 2 }; f(true) + f(false) * 10", 21)]
+    fn test_return_statements(#[case] input: &str, #[case] expected: isize) {
+        assert_eq!(eval(input), Object::Integer(expected));
+    }
+
+    #[rstest]
+    #[case("5 + true;", "type mismatch: INTEGER + BOOLEAN")]
+    #[case("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN")]
+    #[case("-true", "unknown operator: -BOOLEAN")]
+    #[case("true + false;", "unknown operator: BOOLEAN + BOOLEAN")]
+    #[case("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", "unknown operator: BOOLEAN + BOOLEAN")]
+    #[case("\"a\" - \"b\"", "unknown operator: STRING - STRING")]
+    #[case("foobar", "identifier not found: foobar")]
+    #[case("1 / 0", "division by zero")]
+    #[case("let zero = 0; 5 % zero", "modulo by zero")]
+    #[case("(-9223372036854775807 - 1) % -1", "integer overflow")]
+    #[case("(-9223372036854775807 - 1) / -1", "integer overflow")]
+    #[case("true <= false", "unknown operator: BOOLEAN <= BOOLEAN")]
+    #[case("\"a\" >= 1", "type mismatch: STRING >= INTEGER")]
+    #[case("true && x", "identifier not found: x")]
+    #[case("9223372036854775807 + 1", "integer overflow")]
+    #[case("5(1)", "not a function: INTEGER")]
+    #[case("fn(x) { x }(1, 2)", "wrong number of arguments: expected 1, got 2")]
+    #[case("[1, 2, 3][3]", "index out of range: 3 for an array of length 3")]
+    #[case("[1, 2, 3][-1]", "index out of range: -1 for an array of length 3")]
+    #[case("{\"a\": 1}[\"b\"]", "key not found: \"b\"")]
+    #[case("{\"a\": 1}[fn(x) { x }]", "unusable as hash key: FUNCTION")]
+    #[case("{[1]: 1}", "unusable as hash key: ARRAY")]
+    #[case("1[0]", "index operator not supported: INTEGER[INTEGER]")]
+    #[case("[1, x, 3]", "identifier not found: x")]
+    fn test_errors(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval_error(input).message, expected);
+    }
+
+    #[rstest]
+    #[case("5 + true", Span::new(0, 8, 1, 1))]
+    #[case("let x = 1;\nlet y = x * (2 - -true);", Span::new(28, 33, 2, 18))]
+    #[case("1 + foo", Span::new(4, 7, 1, 5))]
+    #[case("let f = fn() { 1 };\n  f(2)", Span::new(22, 26, 2, 3))]
+    #[case("[1][1 + 1]", Span::new(0, 10, 1, 1))]
+    #[case("{\"a\": 1, [2]: 2}", Span::new(9, 12, 1, 10))]
+    fn test_errors_point_at_the_failing_expression(#[case] input: &str, #[case] expected: Span) {
+        assert_eq!(eval_error(input).span, expected);
+    }
+
+    #[test]
+    fn test_errors_carry_the_call_stack() {
+        let input = "let inner = fn(x) { x + true };\n\
+                     let outer = fn(x) { inner(x) };\n\
+                     fn(f) { f(1) }(outer)";
+
+        let error = eval_error(input);
+
+        assert_eq!(error.trace, vec![
+            Frame::new(Some(Symbol::intern("inner")), Span::new(52, 60, 2, 21)),
+            Frame::new(Some(Symbol::intern("outer")), Span::new(72, 76, 3, 9)),
+            Frame::new(None, Span::new(64, 85, 3, 1)),
+        ]);
+        assert_eq!(error.to_string(), "type mismatch: INTEGER + BOOLEAN at 1:21\n\
+                                       \x20   in inner called at 2:21\n\
+                                       \x20   in outer called at 3:9\n\
+                                       \x20   in <anonymous> called at 3:1");
+    }
+
+    #[test]
+    fn test_errors_in_recursion_trace_every_call() {
+        let error = eval_error("let countdown = fn(n) { if (n == 0) { n + \"!\" } else { countdown(n - 1) } }; countdown(3)");
+
+        let names: Vec<Option<Symbol>> = error.trace.iter().map(|frame| frame.function).collect();
+        assert_eq!(names, vec![Some(Symbol::intern("countdown")); 4]);
+        assert_eq!(error.trace[3].call_span.column, 78);
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let input = "let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }; sum(2000)";
+
+        let result = with_stack_for(2001, || eval_with(Evaluator::new().with_max_call_depth(2001), input).to_string());
+
+        assert_eq!(result, "2001000");
+    }
+
+    #[test]
+    fn test_default_call_depth_fits_a_main_thread() {
+        assert!(stack_size(DEFAULT_MAX_CALL_DEPTH) <= 8 << 20);
+    }
+
+    #[test]
+    fn test_unbounded_recursion_is_a_runtime_error() {
+        let error = with_stack_for(DEFAULT_MAX_CALL_DEPTH, || eval_error("let f = fn(n) { f(n + 1) }; f(0)"));
+
+        assert_eq!(error.trace.len(), DEFAULT_MAX_CALL_DEPTH);
+        assert_eq!(error.to_string(), "stack overflow: more than 256 nested calls at 1:17\n\
+                                       \x20   in f called at 1:17\n\
+                                       \x20   ... repeated 254 more times\n\
+                                       \x20   in f called at 1:29");
+    }
+
+    #[test]
+    fn test_max_call_depth_is_configurable() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let program = Parser::new(MonkeyLexer::new(tokenizer, "let f = fn(n) { if (n > 0) { f(n - 1) } }; f(3)")).parse_program();
+        let environment = Rc::new(RefCell::new(Environment::new()));
+
+        let result = Evaluator::new().with_max_call_depth(3).eval_program(&program, &environment);
+
+        assert_eq!(result.to_string(), "ERROR: stack overflow: more than 3 nested calls at 1:30\n\
+                                        \x20   in f called at 1:30\n\
+                                        \x20   ... repeated 1 more time\n\
+                                        \x20   in f called at 1:44");
+        assert_eq!(Evaluator::new().with_max_call_depth(4).eval_program(&program, &environment), Object::Null);
+    }
+
+    #[test]
+    fn test_call_stack_is_unwound_after_an_error() {
+        let environment = Rc::new(RefCell::new(Environment::new()));
+        let mut sut = Evaluator::new();
+        let mut run = |input: &str| {
+            let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+            let program = Parser::new(MonkeyLexer::new(tokenizer, input)).parse_program();
+            sut.eval_program(&program, &environment)
+        };
+
+        run("let f = fn() { fn() { -true }() }; f()");
+        match run("-false") {
+            Object::Error(error) => assert!(error.trace.is_empty()),
+            object => panic!("expected an error, got {:?}", object)
+        }
+    }
+
+    #[rstest]
+    #[case("false && 1 / 0")]
+    #[case("true || 1 % 0")]
+    #[case("false && undefined")]
+    #[case("let f = fn() { len(1) }; true || f()")]
+    fn test_logical_operators_short_circuit(#[case] input: &str) {
+        assert!(!eval(input).is_error());
+    }
+
+    #[rstest]
+    #[case("let a = 5; a;", 5)]
+    #[case("let a = 5 * 5; a;", 25)]
+    #[case("let a = 5; let b = a; b;", 5)]
+    #[case("let a = 5; let b = a; let c = a + b + 5; c;", 15)]
+    #[case("let a = 1; let f = fn() { let a = 2; a }; f() * 10 + a", 21)]
+    fn test_let_statements(#[case] input: &str, #[case] expected: isize) {
+        assert_eq!(eval(input), Object::Integer(expected));
+    }
+
+    #[test]
+    fn test_function_object() {
+        match eval("fn(x) { x + 2; };") {
+            Object::Function(function) => {
+                assert_eq!(function.parameters.len(), 1);
+                assert_eq!(function.to_string(), "fn(x) { x + 2; }");
+            }
+            object => panic!("expected a function, got {:?}", object)
+        }
+    }
+
+    #[rstest]
+    #[case("let identity = fn(x) { x; }; identity(5);", 5)]
+    #[case("let double = fn(x) { x * 2; }; double(5);", 10)]
+    #[case("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20)]
+    #[case("fn(x) { x; }(5)", 5)]
+    #[case("let apply = fn(f, x) { f(x) }; apply(fn(x) { x * x }, 4)", 16)]
+    #[case("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)", 610)]
+    fn test_function_application(#[case] input: &str, #[case] expected: isize) {
+        assert_eq!(eval(input), Object::Integer(expected));
+    }
+
+    #[rstest]
+    #[case("let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3);", 5)]
+    #[case("let x = 1; let f = fn() { x }; let x = 2; f()", 2)]
+    #[case("let make = fn() { let count = 10; fn() { count } }; let count = 1; make()()", 10)]
+    #[case("let compose = fn(f, g) { fn(x) { g(f(x)) } }; compose(fn(x) { x + 1 }, fn(x) { x * 3 })(2)", 9)]
+    fn test_closures(#[case] input: &str, #[case] expected: isize) {
+        assert_eq!(eval(input), Object::Integer(expected));
+    }
+
+    #[rstest]
+    #[case("\"Hello\" + \" \" + \"World!\"", "Hello World!")]
+    #[case("let greet = fn(name) { \"hi \" + name }; greet(\"you\")", "hi you")]
+    fn test_strings(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input), Object::String(Rc::from(expected)));
+    }
+
+    #[rstest]
+    #[case("[1, 2 * 2, 3 + 3]", "[1, 4, 6]")]
+    #[case("[1, 2, 3][0] + [1, 2, 3][2]", "4")]
+    #[case("let a = [1, 2, 3]; let i = a[0]; a[i]", "2")]
+    #[case("[\"a\", [true, fn(x) { x }]]", "[\"a\", [true, fn(x) { x; }]]")]
+    #[case("{\"one\": 10 - 9, \"two\": 1 + 1, true: 3, 4: 4}", "{4: 4, true: 3, \"one\": 1, \"two\": 2}")]
+    #[case("let key = \"k\"; {key: 5}[\"k\"]", "5")]
+    #[case("{true: 1}[1 < 2]", "1")]
+    #[case("{}", "{}")]
+    fn test_arrays_and_hashes(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("len(\"\")", "0")]
+    #[case("len(\"four\")", "4")]
+    #[case("len(\"h\u{e9}llo w\u{f8}rld\")", "11")]
+    #[case("len([1, 2, 3])", "3")]
+    #[case("first([1, 2, 3])", "1")]
+    #[case("first([])", "null")]
+    #[case("last([1, 2, 3])", "3")]
+    #[case("last([])", "null")]
+    #[case("rest([1, 2, 3])", "[2, 3]")]
+    #[case("rest(rest([1]))", "null")]
+    #[case("push([], 1)", "[1]")]
+    #[case("let a = [1]; let b = push(a, 2); [a, b]", "[[1], [1, 2]]")]
+    #[case("puts(\"hello\", 1)", "null")]
+    #[case("puts()", "null")]
+    #[case("len", "builtin function len")]
+    #[case("let map = fn(xs, f) { if (len(xs) == 0) { [] } else { push(map(rest(xs), f), f(first(xs))) } }; map([1, 2, 3], fn(x) { x * 2 })", "[6, 4, 2]")]
+    fn test_builtins(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("len(1)", "argument to `len` must be STRING or ARRAY, got INTEGER")]
+    #[case("len(\"one\", \"two\")", "wrong number of arguments to `len`: expected 1, got 2")]
+    #[case("first(\"abc\")", "argument to `first` must be ARRAY, got STRING")]
+    #[case("last({})", "argument to `last` must be ARRAY, got HASH")]
+    #[case("rest(true)", "argument to `rest` must be ARRAY, got BOOLEAN")]
+    #[case("push(1, 1)", "argument to `push` must be ARRAY, got INTEGER")]
+    #[case("push([])", "wrong number of arguments to `push`: expected 2, got 1")]
+    #[case("len + 1", "type mismatch: BUILTIN + INTEGER")]
+    fn test_builtin_errors(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval_error(input).message, expected);
+    }
+
+    #[test]
+    fn test_builtin_errors_point_at_the_call() {
+        let error = eval_error("let f = fn(xs) { first(xs) };\nf(5)");
+
+        assert_eq!(error.span, Span::new(17, 26, 1, 18));
+        assert_eq!(error.trace, vec![Frame::new(Some(Symbol::intern("f")), Span::new(30, 34, 2, 1))]);
+    }
+
+    #[rstest]
+    #[case("let len = fn(x) { 42 }; len([1])", "42")]
+    #[case("let f = fn(first) { first }; f(1)", "1")]
+    #[case("let f = fn() { len }; let len = 5; f()", "5")]
+    fn test_user_bindings_shadow_builtins(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[test]
+    fn test_builtins_can_be_extended() {
+        let builtins = Builtins::standard().with("sum", None, |arguments| {
+            arguments.iter().try_fold(0, |sum, argument| match argument {
+                Object::Integer(value) => Ok(sum + value),
+                argument => Err(format!("cannot sum {}", argument.type_name()))
+            }).map(Object::Integer)
+        });
+        let mut sut = Evaluator::with_builtins(builtins);
+        sut.builtins_mut().register("answer", Some(0), |_| Ok(Object::Integer(42)));
+        let mut run = |input: &str| {
+            let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+            let program = Parser::new(MonkeyLexer::new(tokenizer, input)).parse_program();
+            sut.eval_program(&program, &Rc::new(RefCell::new(Environment::new()))).to_string()
+        };
+
+        assert_eq!(run("sum(1, 2, len([3])) + answer()"), "46");
+        assert_eq!(run("sum(1, true)"), "ERROR: cannot sum BOOLEAN at 1:1");
+        assert_eq!(run("answer(1)"), "ERROR: wrong number of arguments to `answer`: expected 0, got 1 at 1:1");
+    }
+
+    #[test]
+    fn test_empty_registry() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let program = Parser::new(MonkeyLexer::new(tokenizer, "len")).parse_program();
+        let mut sut = Evaluator::with_builtins(Builtins::new());
+
+        let result = sut.eval_program(&program, &Rc::new(RefCell::new(Environment::new())));
+
+        assert_eq!(result.to_string(), "ERROR: identifier not found: len at 1:1");
+    }
+
+    #[rstest]
+    #[case("let x = 1; x = x + 1; x", "2")]
+    #[case("let x = 1; let f = fn() { x = 10; }; f(); x", "10")]
+    #[case("let x = 1; let f = fn() { let x = 2; x = 3; x }; [f(), x]", "[3, 1]")]
+    #[case("let counter = fn() { let n = 0; fn() { n = n + 1; n } }; let c = counter(); c(); c(); c()", "3")]
+    fn test_assignment(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[test]
+    fn test_assignment_to_undeclared_variable() {
+        let error = eval_error("let f = fn() { y = 1 }; f()");
+
+        assert_eq!(error.message, "assignment to undeclared variable: y");
+        assert_eq!(error.span, Span::new(15, 16, 1, 16));
+    }
+
+    #[rstest]
+    #[case("let i = 0; while (i < 5) { i = i + 1; } i", "5")]
+    #[case("let i = 0; while (false) { i = 1; } i", "0")]
+    #[case("let i = 0; while (true) { i = i + 1; if (i == 3) { break; } } i", "3")]
+    #[case("let i = 0; let odd = []; while (i < 6) { i = i + 1; if (i % 2 == 0) { continue; } odd = push(odd, i); } odd", "[1, 3, 5]")]
+    #[case("let f = fn() { let i = 0; while (true) { i = i + 1; if (i > 4) { return i * 10; } } }; f()", "50")]
+    #[case("let i = 0; while (i < 100000) { i = i + 1; } i", "100000")]
+    #[case("let sum = 0; for (x in [1, 2, 3]) { sum = sum + x; } sum", "6")]
+    #[case("let s = \"\"; for (c in \"h\u{e9}j\") { s = c + s; } s", "j\u{e9}h")]
+    #[case("let n = 0; for (x in []) { n = 1; } n", "0")]
+    #[case("let found = 0; for (x in [3, 8, 5, 9]) { if (x > 4) { found = x; break; } } found", "8")]
+    #[case("let n = 0; for (x in [1, 2, 3, 4]) { if (x % 2 == 1) { continue; } n = n + x; } n", "6")]
+    #[case("let pairs = []; for (a in [1, 2]) { for (b in [1, 2]) { if (b > a) { break; } pairs = push(pairs, [a, b]); } } pairs", "[[1, 1], [2, 1], [2, 2]]")]
+    #[case("let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }); } [fs[0](), fs[1]()]", "[1, 2]")]
+    #[case("for (x in [1]) { x } ", "null")]
+    #[case("let x = 5; for (x in [1]) { } x", "5")]
+    #[case("for (x in 5) { }", "ERROR: cannot iterate over INTEGER at 1:11")]
+    #[case("while (undefined) { }", "ERROR: identifier not found: undefined at 1:8")]
+    #[case("for (x in [1, 2]) { x + true }", "ERROR: type mismatch: INTEGER + BOOLEAN at 1:21")]
+    fn test_loops(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("let n = 0; while (n < 3) { n = n + 1; let x = if (true) { break; }; } n", "1")]
+    #[case("let xs = []; for (i in [1, 2, 3]) { xs = push(xs, if (i == 2) { break; } else { i }); } xs", "[1]")]
+    #[case("let xs = []; for (i in [1, 2, 3]) { xs = push(xs, if (i == 2) { continue; } else { i }); } xs", "[1, 3]")]
+    #[case("let n = 0; for (i in [1, 2]) { n = n + [if (true) { continue; }][0]; } n", "0")]
+    #[case("let n = 0; while (true) { n = n + 1; let h = {\"k\": if (n > 1) { break; }}; } n", "2")]
+    #[case("let f = fn() { let x = if (true) { return 1; }; 2 }; f()", "1")]
+    #[case("let f = fn() { let y = 0; y = if (true) { return 2; }; y }; f()", "2")]
+    #[case("let f = fn() { -if (true) { return 3; } }; f()", "3")]
+    #[case("let f = fn() { 1 + if (true) { return 4; } }; f()", "4")]
+    #[case("let f = fn() { [1][if (true) { return 5; }] }; f()", "5")]
+    #[case("let f = fn() { {if (true) { return 6; }: 1} }; f()", "6")]
+    #[case("let f = fn() { (if (true) { return 7; })() }; f()", "7")]
+    #[case("let f = fn() { len(if (true) { return 8; }) }; f()", "8")]
+    fn test_jumps_out_of_expressions(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(eval(input).to_string(), expected);
+    }
+
+    #[test]
+    fn test_break_in_an_argument_skips_the_call() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let builtins = Builtins::standard().with("puts", None, move |_| {
+            counter.set(counter.get() + 1);
+            Ok(Object::Null)
+        });
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let input = "for (i in [1, 2, 3]) { puts(if (true) { break; }) }";
+        let program = Parser::new(MonkeyLexer::new(tokenizer, input)).parse_program();
+
+        let result = Evaluator::with_builtins(builtins).eval_program(&program, &Rc::new(RefCell::new(Environment::new())));
+
+        assert_eq!(result, Object::Null);
+        assert_eq!(calls.get(), 0);
+    }
+}
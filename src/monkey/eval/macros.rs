@@ -0,0 +1,405 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::sync::LazyLock;
+use crate::lexer::Span;
+use crate::monkey::ast::{BlockStatement, Expression, ExpressionKind, Identifier, Program, Statement};
+use crate::monkey::eval::{Environment, Evaluator, Frame, Function, Object, RuntimeError};
+use crate::monkey::fold::{walk_expression, walk_statement, Fold};
+use crate::symbol::Symbol;
+
+/// How deeply macros can expand to calls of macros before expansion gives up.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// The name of the `unquote` form, which marks the parts of quoted code to evaluate.
+static UNQUOTE: LazyLock<Symbol> = LazyLock::new(|| Symbol::intern("unquote"));
+
+/// The argument of an `unquote(x)` call.
+fn unquote_argument(expression: &Expression) -> Option<&Expression> {
+    match &expression.kind {
+        ExpressionKind::Call { function, arguments } if arguments.len() == 1 &&
+            function.kind == ExpressionKind::Identifier(*UNQUOTE) => Some(&arguments[0]),
+        _ => None
+    }
+}
+
+impl Evaluator {
+    /// Expands the macros of a program before it is evaluated. Top-level `let` statements
+    /// binding a macro literal are taken out of the program and define the macro in
+    /// `environment`, then every call to a macro defined there is replaced by the code the
+    /// macro returns for the code of its arguments.
+    pub fn expand_macros(&mut self, program: Program, environment: &Rc<RefCell<Environment>>) -> Result<Program, RuntimeError> {
+        let mut statements = Vec::new();
+        for statement in program.statements {
+            match statement {
+                Statement::Let { name, value: Expression { kind: ExpressionKind::Macro { parameters, body }, .. } } => {
+                    let definition = Function { name: Some(name.name), parameters, body, environment: environment.clone() };
+                    environment.borrow_mut().set(name.name, Object::Macro(Rc::new(definition)));
+                }
+                statement => statements.push(statement)
+            }
+        }
+        let mut expander = MacroExpander { evaluator: self, environment, depth: 0, error: None };
+        let program = expander.fold_program(Program { statements });
+        match expander.error {
+            Some(error) => Err(error),
+            None => Ok(program)
+        }
+    }
+
+    /// Evaluates `quote(argument)`: the code of the argument, with every `unquote(x)` in it
+    /// replaced by the code for the value of `x`.
+    pub(super) fn eval_quote(&mut self, argument: &Expression, environment: &Rc<RefCell<Environment>>) -> Object {
+        let mut template = argument.clone();
+        if self.hygienic_quotes {
+            template = self.rename_bindings(template);
+        }
+        let mut unquoter = Unquoter { evaluator: self, environment, error: None };
+        let expression = unquoter.fold_expression(template);
+        match unquoter.error {
+            Some(error) => error,
+            None => Object::Quote(Rc::new(expression))
+        }
+    }
+
+    /// Gives every variable the quoted code binds a fresh name that no program can spell, so
+    /// the code a macro returns can neither capture variables of the code passed to it nor
+    /// be captured by them. Code inside `unquote` is the caller's and keeps its names.
+    fn rename_bindings(&mut self, template: Expression) -> Expression {
+        Renamer { scopes: vec![HashMap::new()], gensym_count: &mut self.gensym_count }.fold_expression(template)
+    }
+
+    /// The code for a value spliced into quoted code by `unquote`.
+    fn to_code(&self, value: Object, span: Span) -> Result<Expression, Object> {
+        let kind = match value {
+            Object::Integer(value) => ExpressionKind::Integer(value),
+            Object::Boolean(value) => ExpressionKind::Boolean(value),
+            Object::String(value) => ExpressionKind::String(value.to_string()),
+            Object::Quote(expression) => return Ok(expression.as_ref().clone()),
+            Object::Array(elements) => ExpressionKind::Array(elements.iter()
+                .map(|element| self.to_code(element.clone(), span))
+                .collect::<Result<_, _>>()?),
+            error @ Object::Error(_) => return Err(error),
+            value => return Err(self.error(format!("cannot unquote {}", value.type_name()), span))
+        };
+        Ok(Expression::new(kind, span))
+    }
+
+    /// Runs a macro on the code of its arguments, returning the code it expands to.
+    fn apply_macro(&mut self, definition: &Function, arguments: Vec<Expression>, span: Span) -> Result<Expression, Object> {
+        let name = definition.name.map_or_else(String::new, |name| name.to_string());
+        if arguments.len() != definition.parameters.len() {
+            return Err(self.error(format!("wrong number of arguments to macro `{}`: expected {}, got {}",
+                                          name, definition.parameters.len(), arguments.len()), span));
+        }
+        let mut scope = Environment::enclosed(definition.environment.clone());
+        for (parameter, argument) in definition.parameters.iter().zip(arguments) {
+            scope.set(parameter.name, Object::Quote(Rc::new(argument)));
+        }
+        let hygienic_quotes = mem::replace(&mut self.hygienic_quotes, true);
+        self.call_stack.push(Frame::new(definition.name, span));
+        let result = self.eval_block(&definition.body, &Rc::new(RefCell::new(scope)));
+        self.call_stack.pop();
+        self.hygienic_quotes = hygienic_quotes;
+        let result = match result {
+            Object::ReturnValue(value) => *value,
+            result => result
+        };
+        match result {
+            Object::Quote(expression) => Ok(Expression::new(expression.kind.clone(), span)),
+            error @ Object::Error(_) => Err(error),
+            value => Err(self.error(format!("macro `{}` must return quoted code, got {}", name, value.type_name()), span))
+        }
+    }
+}
+
+/// Replaces calls to the macros defined in an environment by their expansions.
+struct MacroExpander<'e> {
+    evaluator: &'e mut Evaluator,
+    environment: &'e Rc<RefCell<Environment>>,
+    depth: usize,
+    /// The first error, after which expansion stops.
+    error: Option<RuntimeError>,
+}
+
+impl MacroExpander<'_> {
+    fn called_macro(&self, expression: &Expression) -> Option<Rc<Function>> {
+        let ExpressionKind::Call { function, .. } = &expression.kind else { return None };
+        let ExpressionKind::Identifier(name) = function.kind else { return None };
+        match self.environment.borrow().get(name) {
+            Some(Object::Macro(definition)) => Some(definition),
+            _ => None
+        }
+    }
+}
+
+impl Fold for MacroExpander<'_> {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        if self.error.is_some() {
+            return expression;
+        }
+        let Some(definition) = self.called_macro(&expression) else {
+            return walk_expression(self, expression);
+        };
+        if self.depth == MAX_EXPANSION_DEPTH {
+            let error = self.evaluator.error(
+                format!("macro expansion deeper than {} levels", MAX_EXPANSION_DEPTH), expression.span);
+            self.error = Some(into_runtime_error(error));
+            return expression;
+        }
+        let span = expression.span;
+        let ExpressionKind::Call { arguments, .. } = expression.kind else { unreachable!("macro calls are calls") };
+        match self.evaluator.apply_macro(&definition, arguments, span) {
+            Ok(expansion) => {
+                self.depth += 1;
+                let expansion = self.fold_expression(expansion);
+                self.depth -= 1;
+                expansion
+            }
+            Err(error) => {
+                self.error = Some(into_runtime_error(error));
+                Expression::new(ExpressionKind::Array(Vec::new()), span)
+            }
+        }
+    }
+}
+
+fn into_runtime_error(error: Object) -> RuntimeError {
+    match error {
+        Object::Error(error) => error.as_ref().clone(),
+        object => unreachable!("expected an error, got {:?}", object)
+    }
+}
+
+/// Replaces each `unquote(x)` in quoted code by the code for the value of `x`.
+struct Unquoter<'e> {
+    evaluator: &'e mut Evaluator,
+    environment: &'e Rc<RefCell<Environment>>,
+    /// The first error, after which nothing more is evaluated.
+    error: Option<Object>,
+}
+
+impl Fold for Unquoter<'_> {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        let Some(argument) = unquote_argument(&expression) else {
+            return walk_expression(self, expression);
+        };
+        if self.error.is_some() {
+            return expression;
+        }
+        let value = self.evaluator.eval_expression(argument, self.environment);
+        match self.evaluator.to_code(value, expression.span) {
+            Ok(code) => code,
+            Err(error) => {
+                self.error = Some(error);
+                expression
+            }
+        }
+    }
+}
+
+/// Renames the variables quoted code binds, and the references to them within their scope:
+/// a parameter in its function's body, a loop variable in the loop's body, and a `let` in the
+/// code after it, as well as in its own value when that is a function. Scopes are those the
+/// evaluator's environments have, so a `let` in an `if` or `while` block is still in scope after
+/// the block, up to the end of the enclosing function or loop body.
+struct Renamer<'e> {
+    /// The renamed bindings in scope, innermost last. The first holds the bindings the code
+    /// makes where it is spliced in.
+    scopes: Vec<HashMap<Symbol, Symbol>>,
+    gensym_count: &'e mut usize,
+}
+
+impl Renamer<'_> {
+    fn bind(&mut self, identifier: Identifier) -> Identifier {
+        *self.gensym_count += 1;
+        let name = Symbol::intern(&format!("{}@{}", identifier.name, self.gensym_count));
+        self.scopes.last_mut().expect("bindings are made in a scope").insert(identifier.name, name);
+        Identifier::new(name, identifier.span)
+    }
+
+    fn rename(&self, name: Symbol) -> Symbol {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name)).copied().unwrap_or(name)
+    }
+
+    /// Folds a function or macro literal's parameters and body in a scope of their own.
+    fn fold_function(&mut self, parameters: Vec<Identifier>, body: BlockStatement) -> (Vec<Identifier>, BlockStatement) {
+        self.scopes.push(HashMap::new());
+        let parameters = parameters.into_iter().map(|parameter| self.bind(parameter)).collect();
+        let body = self.fold_block(body);
+        self.scopes.pop();
+        (parameters, body)
+    }
+}
+
+impl Fold for Renamer<'_> {
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        match statement {
+            Statement::Let { name, value } if matches!(value.kind, ExpressionKind::Function { .. }) => {
+                let name = self.bind(name);
+                Statement::Let { name, value: self.fold_expression(value) }
+            }
+            Statement::Let { name, value } => {
+                let value = self.fold_expression(value);
+                Statement::Let { name: self.bind(name), value }
+            }
+            Statement::Assign { name, value } => Statement::Assign {
+                name: Identifier::new(self.rename(name.name), name.span),
+                value: self.fold_expression(value),
+            },
+            Statement::For { variable, iterable, body } => {
+                let iterable = self.fold_expression(iterable);
+                self.scopes.push(HashMap::new());
+                let variable = self.bind(variable);
+                let body = self.fold_block(body);
+                self.scopes.pop();
+                Statement::For { variable, iterable, body }
+            }
+            statement => walk_statement(self, statement)
+        }
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        if unquote_argument(&expression).is_some() {
+            return expression;
+        }
+        let kind = match expression.kind {
+            ExpressionKind::Identifier(name) => ExpressionKind::Identifier(self.rename(name)),
+            ExpressionKind::Function { parameters, body } => {
+                let (parameters, body) = self.fold_function(parameters, body);
+                ExpressionKind::Function { parameters, body }
+            }
+            ExpressionKind::Macro { parameters, body } => {
+                let (parameters, body) = self.fold_function(parameters, body);
+                ExpressionKind::Macro { parameters, body }
+            }
+            kind => return walk_expression(self, Expression::new(kind, expression.span))
+        };
+        Expression::new(kind, expression.span)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use rstest::rstest;
+    use crate::monkey::ast::Program;
+    use crate::monkey::eval::{Environment, Evaluator, Object, RuntimeError};
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::token::Tokenize;
+
+    fn parse(input: &str) -> Program {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut parser = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "unexpected parse errors: {:?}", parser.errors());
+        program
+    }
+
+    fn expand(input: &str) -> Result<Program, RuntimeError> {
+        Evaluator::new().expand_macros(parse(input), &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    /// Expands the macros of the input, then evaluates it.
+    fn run(input: &str) -> Object {
+        let mut evaluator = Evaluator::new();
+        let macros = Rc::new(RefCell::new(Environment::new()));
+        match evaluator.expand_macros(parse(input), &macros) {
+            Ok(program) => evaluator.eval_program(&program, &Rc::new(RefCell::new(Environment::new()))),
+            Err(error) => Object::Error(Rc::new(error))
+        }
+    }
+
+    #[rstest]
+    #[case("quote(5)", "QUOTE(5)")]
+    #[case("quote(5 + 8)", "QUOTE(5 + 8)")]
+    #[case("quote(foobar)", "QUOTE(foobar)")]
+    #[case("quote(foobar + barfoo)", "QUOTE(foobar + barfoo)")]
+    #[case("quote(unquote(4))", "QUOTE(4)")]
+    #[case("quote(unquote(4 + 4))", "QUOTE(8)")]
+    #[case("quote(8 + unquote(4 + 4))", "QUOTE(8 + 8)")]
+    #[case("quote(unquote(4 + 4) + 8)", "QUOTE(8 + 8)")]
+    #[case("let foobar = 8; quote(foobar)", "QUOTE(foobar)")]
+    #[case("let foobar = 8; quote(unquote(foobar))", "QUOTE(8)")]
+    #[case("quote(unquote(true))", "QUOTE(true)")]
+    #[case("quote(unquote(true == false))", "QUOTE(false)")]
+    #[case("quote(unquote(\"a\" + \"b\"))", "QUOTE(\"ab\")")]
+    #[case("quote(unquote([1, [2]]))", "QUOTE([1, [2]])")]
+    #[case("quote(unquote(quote(4 + 4)))", "QUOTE(4 + 4)")]
+    #[case("let q = quote(4 + 4); quote(unquote(4 + 4) + unquote(q))", "QUOTE(8 + (4 + 4))")]
+    #[case("quote(fn(x) { let y = x; y })", "QUOTE(fn(x) { let y = x; y; })")]
+    #[case("quote(unquote(len))", "ERROR: cannot unquote BUILTIN at 1:7")]
+    #[case("quote(unquote(fn() { 1 }))", "ERROR: cannot unquote FUNCTION at 1:7")]
+    #[case("quote(unquote(missing))", "ERROR: identifier not found: missing at 1:15")]
+    #[case("quote(1, 2)", "ERROR: wrong number of arguments to `quote`: expected 1, got 2 at 1:1")]
+    fn test_quote_unquote(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(run(input).to_string(), expected);
+    }
+
+    #[test]
+    fn test_macro_definitions_are_removed() {
+        let program = expand("let number = 1; let function = fn(x, y) { x + y }; let mymacro = macro(x, y) { x + y; };")
+            .unwrap();
+
+        assert_eq!(program.to_string(), "let number = 1;\nlet function = fn(x, y) { x + y; };");
+    }
+
+    #[rstest]
+    #[case("let infixExpression = macro() { quote(1 + 2) }; infixExpression()", "1 + 2;")]
+    #[case("let reverse = macro(a, b) { quote(unquote(b) - unquote(a)) }; reverse(2 + 2, 10 - 5)", "10 - 5 - (2 + 2);")]
+    #[case("let unless = macro(condition, consequence, alternative) {\n\
+                quote(if (!(unquote(condition))) { unquote(consequence) } else { unquote(alternative) })\n\
+            }; unless(10 > 5, puts(\"not greater\"), puts(\"greater\"))",
+           "if (!(10 > 5)) { puts(\"not greater\"); } else { puts(\"greater\"); };")]
+    #[case("let twice = macro(x) { quote(unquote(x) + unquote(x)) }; twice(twice(1))", "1 + 1 + (1 + 1);")]
+    #[case("let id = macro(x) { return x; }; id(f(1))", "f(1);")]
+    #[case("let m = macro() { quote(1) }; [m(), fn() { m() }]", "[1, fn() { 1; }];")]
+    fn test_expand_macros(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(expand(input).unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("let m = macro(x) { 5 }; m(1)", "macro `m` must return quoted code, got INTEGER at 1:25")]
+    #[case("let m = macro(x) { quote(x) }; m(1, 2)", "wrong number of arguments to macro `m`: expected 1, got 2 at 1:32")]
+    #[case("let m = macro() { quote(unquote(fn() { 1 })) }; m()", "cannot unquote FUNCTION at 1:25\n    in m called at 1:49")]
+    #[case("let m = macro() { quote(m()) }; m()", "macro expansion deeper than 64 levels at 1:33")]
+    fn test_expansion_errors(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(expand(input).unwrap_err().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("let unless = macro(c, a, b) { quote(if (!(unquote(c))) { unquote(a) } else { unquote(b) }) };\n\
+            unless(10 > 5, 1, 2)", "2")]
+    #[case("let square = macro(x) { quote(unquote(x) * unquote(x)) }; let n = 4; square(n + 1)", "25")]
+    // The temporary of the macro doesn't capture the caller's variable of the same name.
+    #[case("let or_else = macro(a, b) { quote(fn(tmp) { if (tmp) { tmp } else { unquote(b) } }(unquote(a))) };\n\
+            let tmp = 5; or_else(false, tmp)", "5")]
+    #[case("let double = macro(e) { quote(fn() { let x = 2; x * unquote(e) }()) }; let x = 5; double(x)", "10")]
+    #[case("let total = macro(xs) { quote(fn() { let i = 0; for (x in unquote(xs)) { i = i + x; } i }()) };\n\
+            let i = 10; let x = 100; total([i, x])", "110")]
+    // A binding of the macro only captures references within its scope.
+    #[case("let m = macro() { quote([fn(x) { x }(1), x]) }; let x = 5; m()", "[1, 5]")]
+    #[case("let m = macro() { quote(fn() { let a = y; let y = 2; a + y }()) }; let y = 10; m()", "12")]
+    #[case("let m = macro() { quote([for_each(fn(n) { n }), n]) }; let for_each = fn(f) { f(1) }; let n = 7; m()", "[1, 7]")]
+    #[case("let m = macro() { quote(fn() { let f = fn(k) { if (k > 0) { f(k - 1) } else { k } }; f(3) }()) }; m()", "0")]
+    // Like the evaluator, only function and loop bodies are scopes, not `if` and `while` blocks.
+    #[case("let m = macro() { quote(fn() { if (true) { let x = 1; } x }()) }; let x = 5; m()", "1")]
+    #[case("let m = macro() { quote(fn() { let n = 0; while (n < 3) { let y = n; n = n + 1; } y }()) }; m()", "2")]
+    #[case("let m = macro() { quote(if (true) { let x = 1; x }) }; let x = 5; [m(), x]", "[1, 5]")]
+    // Variables the macro doesn't bind still refer to the caller's.
+    #[case("let inc = macro() { quote(fn() { counter = counter + 1; }()) }; let counter = 1; inc(); inc(); counter", "3")]
+    fn test_macros_are_hygienic(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(run(input).to_string(), expected);
+    }
+
+    #[test]
+    fn test_hygienic_names_are_fresh() {
+        let program = expand("let m = macro(x) { quote(fn(y) { let z = y; unquote(x) + z }) }; m(y); m(z)").unwrap();
+
+        assert_eq!(program.to_string(), "fn(y@1) { let z@2 = y@1; y + z@2; };\nfn(y@3) { let z@4 = y@3; z + z@4; };");
+    }
+}
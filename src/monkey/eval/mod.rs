@@ -0,0 +1,13 @@
+#[allow(clippy::module_inception)]
+mod eval;
+mod environment;
+mod object;
+mod runtime_error;
+mod builtins;
+mod macros;
+
+pub use self::eval::{stack_size, Evaluator, DEFAULT_MAX_CALL_DEPTH};
+pub use self::environment::Environment;
+pub use self::object::{Function, HashKey, Object};
+pub use self::runtime_error::{Frame, RuntimeError};
+pub use self::builtins::{Builtin, BuiltinFunction, Builtins};
@@ -0,0 +1,182 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::monkey::ast::{BlockStatement, Expression, Identifier};
+use crate::monkey::eval::{Builtin, Environment, RuntimeError};
+use crate::symbol::Symbol;
+
+/// A Monkey runtime value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(isize),
+    Boolean(bool),
+    String(Rc<str>),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    Null,
+    Function(Rc<Function>),
+    Builtin(Rc<Builtin>),
+    /// A macro, which is a function from code to code run before evaluation.
+    Macro(Rc<Function>),
+    /// Code returned by `quote`.
+    Quote(Rc<Expression>),
+    /// The value of a `return` statement on its way out of the function body.
+    ReturnValue(Box<Object>),
+    /// A `break` on its way out of the loop body.
+    Break,
+    /// A `continue` on its way out of the loop body.
+    Continue,
+    /// A runtime error on its way out of the program, ending evaluation.
+    Error(Rc<RuntimeError>),
+}
+
+/// The values that can be used as keys of a hash.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(isize),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+/// A function or macro literal together with the environment it was defined in. Functions
+/// bound by a `let` statement carry its name for stack traces.
+pub struct Function {
+    pub name: Option<Symbol>,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub environment: Rc<RefCell<Environment>>,
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Macro(_) => "MACRO",
+            Object::Quote(_) => "QUOTE",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Error(_) => "ERROR",
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    /// Whether the value cuts evaluation short: an error, a return value, or a `break` or
+    /// `continue`. Expressions pass these on instead of using them as operands.
+    pub fn is_abrupt(&self) -> bool {
+        matches!(self, Object::Error(_) | Object::ReturnValue(_) | Object::Break | Object::Continue)
+    }
+
+    /// Whether the value counts as true in a condition: everything but `false` and `null`.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    pub fn to_hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Object {
+        match key {
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::String(value) => Object::String(value),
+        }
+    }
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Leaves out the environment, which can refer back to the function itself.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|parameter| parameter.to_string()).collect();
+        write!(f, "fn({}) {}", parameters.join(", "), self.body)
+    }
+}
+
+/// Writes a value the way it is shown inside an array or hash, with strings quoted.
+fn write_element(f: &mut fmt::Formatter<'_>, object: &Object) -> fmt::Result {
+    match object {
+        Object::String(value) => write!(f, "{:?}", value),
+        object => write!(f, "{}", object)
+    }
+}
+
+/// Shows the value as the REPL prints it.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_element(f, element)?;
+                }
+                write!(f, "]")
+            }
+            Object::Hash(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_element(f, &Object::from(key.clone()))?;
+                    write!(f, ": ")?;
+                    write_element(f, value)?;
+                }
+                write!(f, "}}")
+            }
+            Object::Null => write!(f, "null"),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::Macro(definition) => {
+                let parameters: Vec<String> = definition.parameters.iter().map(|parameter| parameter.to_string()).collect();
+                write!(f, "macro({}) {}", parameters.join(", "), definition.body)
+            }
+            Object::Quote(expression) => write!(f, "QUOTE({})", expression),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::Error(error) => write!(f, "ERROR: {}", error),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+use crate::lexer::Span;
+use crate::symbol::Symbol;
+
+/// A function call in progress: the function's name, if it was bound with `let`, and the span
+/// of the call expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: Option<Symbol>,
+    pub call_span: Span,
+}
+
+/// An error that stopped evaluation, with the span of the expression that failed and the
+/// calls that were active, innermost first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+    pub trace: Vec<Frame>,
+}
+
+impl Frame {
+    pub fn new(function: Option<Symbol>, call_span: Span) -> Frame {
+        Frame { function, call_span }
+    }
+}
+
+impl RuntimeError {
+    pub fn new(message: String, span: Span, trace: Vec<Frame>) -> RuntimeError {
+        RuntimeError { message, span, trace }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.function {
+            Some(name) => write!(f, "in {} called at {}", name, self.call_span),
+            None => write!(f, "in <anonymous> called at {}", self.call_span),
+        }
+    }
+}
+
+/// The message and position on the first line, then one line per active call. A run of
+/// identical calls, as recursion makes, is shown once with the number of repeats.
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)?;
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            write!(f, "\n    {}", frame)?;
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            match repeats {
+                0 => {}
+                1 => write!(f, "\n    ... repeated 1 more time")?,
+                _ => write!(f, "\n    ... repeated {} more times", repeats)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::monkey::ast::{BlockStatement, Expression, ExpressionKind, Identifier, Program, Statement};
+
+/// An AST-to-AST transformation. Every method rebuilds its node from folded children by
+/// default, so an implementation only overrides the nodes it changes, calling the matching
+/// `walk_` function to carry on into the children of nodes it keeps.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        Program { statements: program.statements.into_iter().map(|s| self.fold_statement(s)).collect() }
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        walk_statement(self, statement)
+    }
+
+    fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+        BlockStatement {
+            statements: block.statements.into_iter().map(|s| self.fold_statement(s)).collect(),
+            span: block.span,
+        }
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        walk_expression(self, expression)
+    }
+
+    /// Folds an identifier that is bound or assigned rather than read, such as a `let` name
+    /// or a parameter. Identifiers that are read are `ExpressionKind::Identifier` expressions.
+    fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+        identifier
+    }
+}
+
+pub fn walk_statement<F: Fold + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    match statement {
+        Statement::Let { name, value } => Statement::Let {
+            name: folder.fold_identifier(name),
+            value: folder.fold_expression(value),
+        },
+        Statement::Assign { name, value } => Statement::Assign {
+            name: folder.fold_identifier(name),
+            value: folder.fold_expression(value),
+        },
+        Statement::Return(value) => Statement::Return(folder.fold_expression(value)),
+        Statement::Expression(expression) => Statement::Expression(folder.fold_expression(expression)),
+        Statement::While { condition, body } => Statement::While {
+            condition: folder.fold_expression(condition),
+            body: folder.fold_block(body),
+        },
+        Statement::For { variable, iterable, body } => Statement::For {
+            variable: folder.fold_identifier(variable),
+            iterable: folder.fold_expression(iterable),
+            body: folder.fold_block(body),
+        },
+        Statement::Break => Statement::Break,
+        Statement::Continue => Statement::Continue,
+    }
+}
+
+pub fn walk_expression<F: Fold + ?Sized>(folder: &mut F, expression: Expression) -> Expression {
+    let kind = match expression.kind {
+        kind @ (ExpressionKind::Identifier(_) | ExpressionKind::Integer(_) |
+                ExpressionKind::Boolean(_) | ExpressionKind::String(_)) => kind,
+        ExpressionKind::Prefix { operator, right } => {
+            ExpressionKind::Prefix { operator, right: fold_boxed(folder, right) }
+        }
+        ExpressionKind::Infix { operator, left, right } => ExpressionKind::Infix {
+            operator,
+            left: fold_boxed(folder, left),
+            right: fold_boxed(folder, right),
+        },
+        ExpressionKind::If { condition, consequence, alternative } => ExpressionKind::If {
+            condition: fold_boxed(folder, condition),
+            consequence: folder.fold_block(consequence),
+            alternative: alternative.map(|alternative| folder.fold_block(alternative)),
+        },
+        ExpressionKind::Function { parameters, body } => ExpressionKind::Function {
+            parameters: parameters.into_iter().map(|p| folder.fold_identifier(p)).collect(),
+            body: folder.fold_block(body),
+        },
+        ExpressionKind::Macro { parameters, body } => ExpressionKind::Macro {
+            parameters: parameters.into_iter().map(|p| folder.fold_identifier(p)).collect(),
+            body: folder.fold_block(body),
+        },
+        ExpressionKind::Call { function, arguments } => ExpressionKind::Call {
+            function: fold_boxed(folder, function),
+            arguments: arguments.into_iter().map(|a| folder.fold_expression(a)).collect(),
+        },
+        ExpressionKind::Array(elements) => {
+            ExpressionKind::Array(elements.into_iter().map(|e| folder.fold_expression(e)).collect())
+        }
+        ExpressionKind::Hash(pairs) => ExpressionKind::Hash(pairs.into_iter()
+            .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+            .collect()),
+        ExpressionKind::Index { left, index } => ExpressionKind::Index {
+            left: fold_boxed(folder, left),
+            index: fold_boxed(folder, index),
+        },
+    };
+    Expression::new(kind, expression.span)
+}
+
+/// Folds a boxed child, reusing its allocation.
+fn fold_boxed<F: Fold + ?Sized>(folder: &mut F, mut expression: Box<Expression>) -> Box<Expression> {
+    let placeholder = Expression::new(ExpressionKind::Boolean(false), expression.span);
+    *expression = folder.fold_expression(std::mem::replace(&mut *expression, placeholder));
+    expression
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use crate::monkey::ast::{Expression, ExpressionKind, Identifier, Program};
+    use crate::monkey::fold::{walk_expression, Fold};
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::symbol::Symbol;
+    use crate::token::Tokenize;
+
+    fn parse(input: &str) -> Program {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        Parser::new(MonkeyLexer::new(tokenizer, input)).parse_program()
+    }
+
+    /// Turns every `1` into a `2`.
+    struct OneToTwo;
+
+    impl Fold for OneToTwo {
+        fn fold_expression(&mut self, expression: Expression) -> Expression {
+            match expression.kind {
+                ExpressionKind::Integer(1) => Expression::new(ExpressionKind::Integer(2), expression.span),
+                _ => walk_expression(self, expression)
+            }
+        }
+    }
+
+    /// Uppercases every name, bound or read.
+    struct Shout;
+
+    impl Fold for Shout {
+        fn fold_expression(&mut self, expression: Expression) -> Expression {
+            match expression.kind {
+                ExpressionKind::Identifier(name) => {
+                    let name = Symbol::intern(&name.to_string().to_uppercase());
+                    Expression::new(ExpressionKind::Identifier(name), expression.span)
+                }
+                _ => walk_expression(self, expression)
+            }
+        }
+
+        fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+            Identifier::new(Symbol::intern(&identifier.name.to_string().to_uppercase()), identifier.span)
+        }
+    }
+
+    #[test]
+    fn test_fold_reaches_every_expression() {
+        let input = "let a = 1; a = -1 + 1; return [1, {1: 1}][1];\n\
+                     if (1) { 1 } else { fn(x) { 1 }(1) };\n\
+                     while (1) { for (x in 1) { macro() { 1 } } }";
+
+        assert_eq!(OneToTwo.fold_program(parse(input)).to_string(), "let a = 2;\n\
+                    a = -2 + 2;\n\
+                    return [2, {2: 2}][2];\n\
+                    if (2) { 2; } else { fn(x) { 2; }(2); };\n\
+                    while (2) { for (x in 2) { macro() { 2; }; } }");
+    }
+
+    #[test]
+    fn test_fold_reaches_every_identifier() {
+        let input = "let a = fn(b) { for (c in b) { d = c; } }; a";
+
+        assert_eq!(Shout.fold_program(parse(input)).to_string(),
+                   "let A = fn(B) { for (C in B) { D = C; } };\nA;");
+    }
+
+    #[test]
+    fn test_fold_keeps_spans() {
+        let program = parse("f(1)");
+        let folded = OneToTwo.fold_program(program.clone());
+
+        assert_eq!(format!("{:?}", folded).replace("Integer(2)", "Integer(1)"), format!("{:?}", program));
+    }
+}
@@ -1,16 +1,20 @@
 use crate::lexer::Lexer;
 use crate::monkey::token::MonkeyToken;
 
-pub type MonkeyLexer<'a> = Lexer<'a, MonkeyToken>;
+pub type MonkeyLexer<'a> = Lexer<'a, MonkeyToken<'a>>;
 
 #[cfg(test)]
 mod test {
+    use std::borrow::Cow;
     use std::rc::Rc;
     use rstest::rstest;
     use crate::monkey::lexer::MonkeyLexer;
     use crate::monkey::token::MonkeyTokenizer;
     use crate::monkey::token::MonkeyToken;
-    use crate::token::Tokenize;
+    use crate::monkey::token::MonkeyErrorKind;
+    use crate::symbol::Symbol;
+    use crate::token::{CharacterSet, Tokenize};
+    use crate::lexer::{LexicalError, LocatedToken, Span};
 
     #[rstest]
     #[case("=", MonkeyToken::Assign)]
@@ -21,14 +25,22 @@ mod test {
     #[case("-", MonkeyToken::Minus)]
     #[case("*", MonkeyToken::Asteriks)]
     #[case("/", MonkeyToken::Slash)]
+    #[case("%", MonkeyToken::Percent)]
     #[case("<", MonkeyToken::LT)]
     #[case(">", MonkeyToken::GT)]
+    #[case("<=", MonkeyToken::LTE)]
+    #[case(">=", MonkeyToken::GTE)]
+    #[case("&&", MonkeyToken::And)]
+    #[case("||", MonkeyToken::Or)]
     #[case(";", MonkeyToken::Semicolon)]
     #[case("(", MonkeyToken::LParen)]
     #[case(")", MonkeyToken::RParen)]
     #[case("{", MonkeyToken::LBrace)]
     #[case("}", MonkeyToken::RBrace)]
     #[case(",", MonkeyToken::Comma)]
+    #[case(":", MonkeyToken::Colon)]
+    #[case("[", MonkeyToken::LBracket)]
+    #[case("]", MonkeyToken::RBracket)]
     #[case("fn", MonkeyToken::Function)]
     #[case("let", MonkeyToken::Let)]
     #[case("true", MonkeyToken::True)]
@@ -36,58 +48,509 @@ mod test {
     #[case("if", MonkeyToken::If)]
     #[case("else", MonkeyToken::Else)]
     #[case("return", MonkeyToken::Return)]
+    #[case("while", MonkeyToken::While)]
+    #[case("for", MonkeyToken::For)]
+    #[case("in", MonkeyToken::In)]
+    #[case("break", MonkeyToken::Break)]
+    #[case("continue", MonkeyToken::Continue)]
+    #[case("macro", MonkeyToken::Macro)]
     fn test_symbols_and_keywords(#[case] input: &str, #[case] expected: MonkeyToken) {
-        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
         let mut sut = MonkeyLexer::new(tokenizer, input);
 
-        assert_eq!(sut.next_token(), expected);
-        assert_eq!(sut.next_token(), MonkeyToken::EOF);
+        assert_eq!(sut.next_token().token, expected);
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
     }
 
     #[test]
     fn test_ints() {
-        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
         let mut sut = MonkeyLexer::new(tokenizer, "2345");
 
-        assert_eq!(sut.next_token(), MonkeyToken::Int(2345));
-        assert_eq!(sut.next_token(), MonkeyToken::EOF)
+        assert_eq!(sut.next_token().token, MonkeyToken::Int(2345));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
     }
 
     #[test]
     fn test_idents() {
-        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
         let mut sut = MonkeyLexer::new(tokenizer, "monkey");
 
-        assert_eq!(sut.next_token(), MonkeyToken::Ident(String::from("monkey")));
-        assert_eq!(sut.next_token(), MonkeyToken::EOF)
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("monkey")));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
     }
 
     #[test]
     fn test_multiple_tokens() {
-        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer{});
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
         let mut sut = MonkeyLexer::new(tokenizer, "let monkey = true");
 
-        assert_eq!(sut.next_token(), MonkeyToken::Let);
-        assert_eq!(sut.next_token(), MonkeyToken::Ident(String::from("monkey")));
-        assert_eq!(sut.next_token(), MonkeyToken::Assign);
-        assert_eq!(sut.next_token(), MonkeyToken::True);
-        assert_eq!(sut.next_token(), MonkeyToken::EOF)
+        assert_eq!(sut.next_token().token, MonkeyToken::Let);
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("monkey")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Assign);
+        assert_eq!(sut.next_token().token, MonkeyToken::True);
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
     }
 
     #[test]
     fn test_edge_cases() {
-        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer{});
-        let mut sut = MonkeyLexer::new(tokenizer, "monkey123 <= ! = 98;nam");
-
-        assert_eq!(sut.next_token(), MonkeyToken::Ident(String::from("monkey")));
-        assert_eq!(sut.next_token(), MonkeyToken::Int(123));
-        assert_eq!(sut.next_token(), MonkeyToken::LT);
-        assert_eq!(sut.next_token(), MonkeyToken::Assign);
-        assert_eq!(sut.next_token(), MonkeyToken::Bang);
-        assert_eq!(sut.next_token(), MonkeyToken::Assign);
-        assert_eq!(sut.next_token(), MonkeyToken::Int(98));
-        assert_eq!(sut.next_token(), MonkeyToken::Semicolon);
-        assert_eq!(sut.next_token(), MonkeyToken::Ident(String::from("nam")));
-        assert_eq!(sut.next_token(), MonkeyToken::EOF)
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "monkey123 <= ! = 98;nam x_1 _2");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("monkey123")));
+        assert_eq!(sut.next_token().token, MonkeyToken::LTE);
+        assert_eq!(sut.next_token().token, MonkeyToken::Bang);
+        assert_eq!(sut.next_token().token, MonkeyToken::Assign);
+        assert_eq!(sut.next_token().token, MonkeyToken::Int(98));
+        assert_eq!(sut.next_token().token, MonkeyToken::Semicolon);
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("nam")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x_1")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("_2")));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
+    }
+
+    #[test]
+    fn test_legacy_identifiers() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new().with_legacy_identifiers());
+        let mut sut = MonkeyLexer::new(tokenizer, "monkey123 1x");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("monkey")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Int(123));
+        assert_eq!(sut.next_token().token, MonkeyToken::Int(1));
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "let x\r\n  == 5;\nfoo");
+
+        assert_eq!(sut.next_token().span, Span::new(0, 3, 1, 1));
+        assert_eq!(sut.next_token().span, Span::new(4, 5, 1, 5));
+        assert_eq!(sut.next_token().span, Span::new(9, 11, 2, 3));
+        assert_eq!(sut.next_token().span, Span::new(12, 13, 2, 6));
+        assert_eq!(sut.next_token().span, Span::new(13, 14, 2, 7));
+        assert_eq!(sut.next_token().span, Span::new(15, 18, 3, 1));
+        assert_eq!(sut.next_token().span, Span::new(18, 18, 3, 4));
+    }
+
+    #[test]
+    fn test_multi_byte_input() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "x §€ y");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        let token = sut.next_token();
+        assert_eq!(token.token, MonkeyToken::Illegal);
+        assert_eq!(token.span, Span::new(2, 4, 1, 3));
+        let token = sut.next_token();
+        assert_eq!(token.token, MonkeyToken::Illegal);
+        assert_eq!(token.span, Span::new(4, 7, 1, 4));
+        let token = sut.next_token();
+        assert_eq!(token.token, MonkeyToken::Ident(Symbol::intern("y")));
+        assert_eq!(token.span, Span::new(8, 9, 1, 6));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF)
+    }
+
+    #[test]
+    fn test_large_input() {
+        let input = "let x = 12345;\n".repeat(20_000);
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, &input);
+
+        let mut count = 0;
+        let mut last = sut.next_token();
+        while last.token != MonkeyToken::EOF {
+            count += 1;
+            last = sut.next_token();
+        }
+        assert_eq!(count, 100_000);
+        assert_eq!(last.span, Span::new(input.len(), input.len(), 20_001, 1));
+    }
+
+    #[test]
+    fn test_iterator_ends_after_eof() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let sut = MonkeyLexer::new(tokenizer, "let x = 5;");
+
+        let tokens: Vec<MonkeyToken> = sut.map(|t| t.token).collect();
+        assert_eq!(tokens, vec![
+            MonkeyToken::Let,
+            MonkeyToken::Ident(Symbol::intern("x")),
+            MonkeyToken::Assign,
+            MonkeyToken::Int(5),
+            MonkeyToken::Semicolon,
+            MonkeyToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens = MonkeyLexer::tokenize(tokenizer, "x+ 1");
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token, MonkeyToken::Ident(Symbol::intern("x")));
+        assert_eq!(tokens[0].span, Span::new(0, 1, 1, 1));
+        assert_eq!(tokens[1].token, MonkeyToken::Plus);
+        assert_eq!(tokens[1].span, Span::new(1, 2, 1, 2));
+        assert_eq!(tokens[2].token, MonkeyToken::Int(1));
+        assert_eq!(tokens[2].span, Span::new(3, 4, 1, 4));
+        assert_eq!(tokens[3].token, MonkeyToken::EOF);
+        assert_eq!(tokens[3].span, Span::new(4, 4, 1, 5));
+    }
+
+    #[test]
+    fn test_tokenize_empty_input() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens = MonkeyLexer::tokenize(tokenizer, "");
+
+        assert_eq!(tokens, vec![LocatedToken::new(MonkeyToken::EOF, Span::new(0, 0, 1, 1))]);
+    }
+
+    #[test]
+    fn test_errors_are_reported_and_lexing_continues() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "let @ = 5;\n# x");
+
+        let tokens: Vec<MonkeyToken> = sut.by_ref().map(|t| t.token).collect();
+        assert_eq!(tokens, vec![
+            MonkeyToken::Let,
+            MonkeyToken::Illegal,
+            MonkeyToken::Assign,
+            MonkeyToken::Int(5),
+            MonkeyToken::Semicolon,
+            MonkeyToken::Illegal,
+            MonkeyToken::Ident(Symbol::intern("x")),
+            MonkeyToken::EOF
+        ]);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(MonkeyErrorKind::UnexpectedCharacter('@'), "@", Span::new(4, 5, 1, 5)),
+            LexicalError::new(MonkeyErrorKind::UnexpectedCharacter('#'), "#", Span::new(11, 12, 2, 1)),
+        ]);
+        assert_eq!(sut.errors()[0].message(), "unexpected character '@'");
+        assert_eq!(sut.errors()[1].to_string(), "unexpected character '#' at 2:1");
+    }
+
+    #[test]
+    fn test_largest_int() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let input = isize::MAX.to_string();
+        let mut sut = MonkeyLexer::new(tokenizer, &input);
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Int(isize::MAX));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert!(sut.errors().is_empty());
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn test_int_out_of_range() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "x = 99999999999999999999;");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Assign);
+        assert_eq!(sut.next_token().token, MonkeyToken::Illegal);
+        assert_eq!(sut.next_token().token, MonkeyToken::Semicolon);
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(MonkeyErrorKind::IntegerOutOfRange, "99999999999999999999", Span::new(4, 24, 1, 5)),
+        ]);
+        assert_eq!(sut.errors()[0].message(), "integer literal out of range");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_big_int() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "99999999999999999999");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::BigInt("99999999999999999999".parse().unwrap()));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert!(sut.errors().is_empty());
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens: Vec<MonkeyToken> = MonkeyLexer::new(tokenizer, "// answer\r\nlet x /* = 4 */ = 10 / 2; // done")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            MonkeyToken::Let,
+            MonkeyToken::Ident(Symbol::intern("x")),
+            MonkeyToken::Assign,
+            MonkeyToken::Int(10),
+            MonkeyToken::Slash,
+            MonkeyToken::Int(2),
+            MonkeyToken::Semicolon,
+            MonkeyToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_comments_as_tokens() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "// answer\r\nx /* y\n */").with_comments();
+
+        let token = sut.next_token();
+        assert_eq!(token.token, MonkeyToken::Comment("// answer"));
+        assert_eq!(token.span, Span::new(0, 9, 1, 1));
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        let token = sut.next_token();
+        assert_eq!(token.token, MonkeyToken::Comment("/* y\n */"));
+        assert_eq!(token.span, Span::new(13, 21, 2, 3));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "x /*/ y");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(MonkeyErrorKind::UnterminatedComment, "/*/ y", Span::new(2, 7, 1, 3)),
+        ]);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("   ")]
+    #[case("let five = 5;")]
+    #[case("  let add = fn(x, y) {\r\n\tx + y; // sum\r\n};\n\n")]
+    #[case("/* header */\nif (a != b) { return a; } else { return @ 12; } /* open")]
+    #[case("let größe = 99999999999999999999;  \n")]
+    #[case("puts(\"a \\\"quoted\\\" \\q\", \"open")]
+    fn test_lossless_round_trip(#[case] input: &str) {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let sut = MonkeyLexer::new(tokenizer, input).lossless();
+
+        assert_eq!(sut.to_source(), input);
+    }
+
+    #[test]
+    fn test_lossless_trivia() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens: Vec<_> = MonkeyLexer::new(tokenizer, "fn x /* a */ // b\r\n  ; ").lossless().collect();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token.token, MonkeyToken::Function);
+        assert_eq!((tokens[0].leading_trivia, tokens[0].text, tokens[0].trailing_trivia), ("", "fn", " "));
+        assert_eq!((tokens[1].leading_trivia, tokens[1].text, tokens[1].trailing_trivia), ("", "x", " /* a */ // b"));
+        assert_eq!((tokens[2].leading_trivia, tokens[2].text, tokens[2].trailing_trivia), ("\r\n  ", ";", " "));
+        assert_eq!(tokens[3].token.token, MonkeyToken::EOF);
+        assert_eq!((tokens[3].leading_trivia, tokens[3].text, tokens[3].trailing_trivia), ("", "", ""));
+    }
+
+    #[test]
+    fn test_comments_borrow_from_input() {
+        let input = String::from("let monkey = 5; // five");
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let sut = MonkeyLexer::new(tokenizer, &input).with_comments();
+
+        let comment = sut.map(|t| t.token).nth(5);
+        match comment {
+            Some(MonkeyToken::Comment(text)) => assert!(std::ptr::eq(text, &input[16..])),
+            token => panic!("expected a comment, got {:?}", token)
+        }
+    }
+
+    #[test]
+    fn test_idents_are_interned() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens = MonkeyLexer::tokenize(tokenizer, "x + y * x");
+
+        assert_eq!(tokens[0].token, tokens[4].token);
+        assert_ne!(tokens[0].token, tokens[2].token);
+        match tokens[4].token {
+            MonkeyToken::Ident(symbol) => assert_eq!(symbol.name(), "x"),
+            ref token => panic!("expected an identifier, got {:?}", token)
+        }
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens: Vec<MonkeyToken> = MonkeyLexer::new(tokenizer, "let længde\u{00A0}=\u{2003}größe;\u{2028}_π")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            MonkeyToken::Let,
+            MonkeyToken::Ident(Symbol::intern("længde")),
+            MonkeyToken::Assign,
+            MonkeyToken::Ident(Symbol::intern("größe")),
+            MonkeyToken::Semicolon,
+            MonkeyToken::Ident(Symbol::intern("_π")),
+            MonkeyToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_ascii_character_set() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new().with_character_set(CharacterSet::Ascii));
+        let tokens: Vec<MonkeyToken> = MonkeyLexer::new(tokenizer, "længde\u{00A0}x")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            MonkeyToken::Ident(Symbol::intern("l")),
+            MonkeyToken::Illegal,
+            MonkeyToken::Ident(Symbol::intern("ngde")),
+            MonkeyToken::Illegal,
+            MonkeyToken::Ident(Symbol::intern("x")),
+            MonkeyToken::EOF
+        ]);
+    }
+
+    #[rstest]
+    #[case(r#""""#, "")]
+    #[case(r#""hello world""#, "hello world")]
+    #[case(r#""a\nb\tc""#, "a\nb\tc")]
+    #[case(r#""say \"hi\"""#, "say \"hi\"")]
+    #[case(r#""back\\slash\\""#, "back\\slash\\")]
+    #[case(r#""\u{48}\u{e6}\u{1F600}""#, "Hæ😀")]
+    #[case("\"two\nlines\"", "two\nlines")]
+    fn test_strings(#[case] input: &str, #[case] expected: &str) {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, input);
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Str(expected.into()));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert!(sut.errors().is_empty());
+    }
+
+    #[test]
+    fn test_string_without_escapes_is_borrowed() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, r#""plain" "esc\n""#);
+
+        assert!(matches!(sut.next_token().token, MonkeyToken::Str(Cow::Borrowed("plain"))));
+        assert!(matches!(sut.next_token().token, MonkeyToken::Str(Cow::Owned(_))));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "let s = \"abc\\\";");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Let);
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("s")));
+        assert_eq!(sut.next_token().token, MonkeyToken::Assign);
+        assert_eq!(sut.next_token(), LocatedToken::new(MonkeyToken::Illegal, Span::new(8, 15, 1, 9)));
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(MonkeyErrorKind::UnterminatedString, "\"abc\\\";", Span::new(8, 15, 1, 9)),
+        ]);
+        assert_eq!(sut.errors()[0].to_string(), "unterminated string literal at 1:9");
+    }
+
+    #[rstest]
+    #[case(r#""ok\q""#, r"\q", 3)]
+    #[case(r#""\u{110000}""#, r"\u{110000}", 1)]
+    #[case(r#""\u{}""#, r"\u{}", 1)]
+    #[case(r#""\u{1234567}""#, r"\u{1234567}", 1)]
+    #[case(r#""\u41""#, r"\u", 1)]
+    #[case(r#""\u{41""#, r"\u{41", 1)]
+    #[case(r#""a\ø""#, r"\ø", 2)]
+    fn test_invalid_escapes(#[case] input: &str, #[case] escape: &str, #[case] at: usize) {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, input);
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Illegal);
+        assert_eq!(sut.next_token().token, MonkeyToken::EOF);
+        assert_eq!(sut.errors(), &[
+            LexicalError::new(
+                MonkeyErrorKind::InvalidEscape { escape: escape.to_string(), at },
+                escape,
+                Span::new(at, at + escape.len(), 1, at + 1)),
+        ]);
+        assert_eq!(sut.errors()[0].message(), format!("invalid escape sequence '{}'", escape));
+    }
+
+    #[test]
+    fn test_invalid_escape_location_spans_lines() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, "x\n  \"first\nsecond \\x\" y");
+
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("x")));
+        assert_eq!(sut.next_token(), LocatedToken::new(MonkeyToken::Illegal, Span::new(4, 21, 2, 3)));
+        assert_eq!(sut.next_token().token, MonkeyToken::Ident(Symbol::intern("y")));
+        assert_eq!(sut.errors()[0].span, Span::new(18, 20, 3, 8));
+        assert_eq!(sut.errors()[0].to_string(), "invalid escape sequence '\\x' at 3:8");
+    }
+
+    #[test]
+    fn test_array_hash_and_index_tokens() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens: Vec<MonkeyToken> = MonkeyLexer::new(tokenizer, r#"[1, 2][0]; {"a": 1}["a"]"#)
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            MonkeyToken::LBracket,
+            MonkeyToken::Int(1),
+            MonkeyToken::Comma,
+            MonkeyToken::Int(2),
+            MonkeyToken::RBracket,
+            MonkeyToken::LBracket,
+            MonkeyToken::Int(0),
+            MonkeyToken::RBracket,
+            MonkeyToken::Semicolon,
+            MonkeyToken::LBrace,
+            MonkeyToken::Str("a".into()),
+            MonkeyToken::Colon,
+            MonkeyToken::Int(1),
+            MonkeyToken::RBrace,
+            MonkeyToken::LBracket,
+            MonkeyToken::Str("a".into()),
+            MonkeyToken::RBracket,
+            MonkeyToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_comparison_and_logical_operators() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let tokens: Vec<MonkeyToken> = MonkeyLexer::new(tokenizer, "a<=b>=c<d||!e&&f%2")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            MonkeyToken::Ident(Symbol::intern("a")),
+            MonkeyToken::LTE,
+            MonkeyToken::Ident(Symbol::intern("b")),
+            MonkeyToken::GTE,
+            MonkeyToken::Ident(Symbol::intern("c")),
+            MonkeyToken::LT,
+            MonkeyToken::Ident(Symbol::intern("d")),
+            MonkeyToken::Or,
+            MonkeyToken::Bang,
+            MonkeyToken::Ident(Symbol::intern("e")),
+            MonkeyToken::And,
+            MonkeyToken::Ident(Symbol::intern("f")),
+            MonkeyToken::Percent,
+            MonkeyToken::Int(2),
+            MonkeyToken::EOF,
+        ]);
+    }
+
+    #[rstest]
+    #[case("a & b", '&', 2)]
+    #[case("a | b", '|', 2)]
+    fn test_single_ampersand_and_bar_are_errors(#[case] input: &str, #[case] ch: char, #[case] start: usize) {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = MonkeyLexer::new(tokenizer, input);
+        let tokens: Vec<MonkeyToken> = sut.by_ref().map(|t| t.token).collect();
+
+        assert_eq!(tokens[1], MonkeyToken::Illegal);
+        assert_eq!(tokens[2], MonkeyToken::Ident(Symbol::intern("b")));
+        assert_eq!(sut.errors()[0].kind, MonkeyErrorKind::UnexpectedCharacter(ch));
+        assert_eq!(sut.errors()[0].span.start, start);
     }
 }
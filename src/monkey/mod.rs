@@ -1,3 +1,8 @@
-mod token;
-mod lexer;
-
+pub mod token;
+pub mod lexer;
+pub mod ast;
+pub mod parse_error;
+pub mod parser;
+pub mod fold;
+pub mod eval;
+pub mod compiler;
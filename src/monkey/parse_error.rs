@@ -0,0 +1,51 @@
+use std::fmt;
+use crate::lexer::Span;
+use crate::monkey::token::MonkeyErrorKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// A token other than the one the grammar allows here. Both sides are already described
+    /// for the message, such as "`)`", "an expression" or "end of input".
+    Expected { expected: String, found: String },
+    /// Text the lexer could not turn into a token.
+    Lexical(MonkeyErrorKind),
+    /// A `break` or `continue` that is not inside a loop of the function it is in.
+    OutsideLoop { keyword: String },
+}
+
+/// A syntax error found by the Monkey parser, and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Span) -> ParseError {
+        ParseError { kind, span }
+    }
+
+    pub fn expected(expected: impl Into<String>, found: impl Into<String>, span: Span) -> ParseError {
+        ParseError::new(ParseErrorKind::Expected { expected: expected.into(), found: found.into() }, span)
+    }
+
+    pub fn message(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Expected { expected, found } => write!(f, "expected {} but found {}", expected, found),
+            ParseErrorKind::Lexical(kind) => write!(f, "{}", kind),
+            ParseErrorKind::OutsideLoop { keyword } => write!(f, "`{}` outside of a loop", keyword),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
+    }
+}
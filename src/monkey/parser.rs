@@ -0,0 +1,829 @@
+use std::mem;
+use crate::lexer::{LocatedToken, Span};
+use crate::monkey::ast::{BlockStatement, Expression, ExpressionKind, Identifier, InfixOperator, Precedence, PrefixOperator, Program, Statement};
+use crate::monkey::lexer::MonkeyLexer;
+use crate::monkey::parse_error::{ParseError, ParseErrorKind};
+#[cfg(feature = "bigint")]
+use crate::monkey::token::MonkeyErrorKind;
+use crate::monkey::token::MonkeyToken;
+use crate::token::Token;
+
+/// The precedence of `token` when it follows an expression.
+fn precedence_of(token: &MonkeyToken) -> Precedence {
+    match token {
+        MonkeyToken::LParen | MonkeyToken::LBracket => Precedence::Postfix,
+        token => Parser::infix_operator(token).map_or(Precedence::Lowest, |operator| operator.precedence())
+    }
+}
+
+/// A Pratt parser turning the tokens of a `MonkeyLexer` into a `Program`.
+pub struct Parser<'a> {
+    lexer: MonkeyLexer<'a>,
+    current: LocatedToken<MonkeyToken<'a>>,
+    peek: LocatedToken<MonkeyToken<'a>>,
+    errors: Vec<ParseError>,
+    /// How many loops of the current function body the parser is in.
+    loop_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(lexer: MonkeyLexer<'a>) -> Parser<'a> {
+        let placeholder = || LocatedToken::new(MonkeyToken::EOF, Span::new(0, 0, 1, 1));
+        let mut parser = Parser { lexer, current: placeholder(), peek: placeholder(), errors: Vec::new(), loop_depth: 0 };
+        parser.next_token();
+        parser.next_token();
+        parser
+    }
+
+    /// Parses the whole input. A statement with a syntax error is left out of the program,
+    /// the error is reported through `errors`, and parsing resumes after the next `;` or `}`.
+    pub fn parse_program(&mut self) -> Program {
+        let mut program = Program::default();
+        while self.current.token != MonkeyToken::EOF {
+            match self.parse_statement() {
+                Ok(Some(statement)) => program.statements.push(statement),
+                Ok(None) => {}
+                Err(error) => {
+                    self.recover(error);
+                }
+            }
+            self.next_token();
+        }
+        program
+    }
+
+    /// The lexical and syntax errors found so far, in source order.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    fn next_token(&mut self) {
+        let next = loop {
+            let token = self.lexer.next_token();
+            match token.token {
+                MonkeyToken::Comment(_) => continue,
+                MonkeyToken::Illegal => {
+                    if let Some(error) = self.lexer.errors().last() {
+                        self.record(ParseError::new(ParseErrorKind::Lexical(error.kind.clone()), error.span));
+                    }
+                }
+                _ => {}
+            }
+            break token;
+        };
+        self.current = mem::replace(&mut self.peek, next);
+    }
+
+    /// Adds an error, keeping the list in source order. An illegal token is reported once by
+    /// the lexer, so the parser tripping over it again adds nothing.
+    fn record(&mut self, error: ParseError) {
+        if !self.errors.contains(&error) {
+            let index = self.errors.partition_point(|e| e.span.start <= error.span.start);
+            self.errors.insert(index, error);
+        }
+    }
+
+    /// Skips to the end of the statement that failed to parse: the current token becomes the
+    /// next `;`, the `}` closing braces opened after the error, or the `}` of the enclosing
+    /// block, which is what the returned flag tells.
+    fn synchronize(&mut self) -> bool {
+        let mut depth = 0;
+        loop {
+            match self.current.token {
+                MonkeyToken::EOF => return false,
+                MonkeyToken::Semicolon if depth == 0 => return false,
+                MonkeyToken::RBrace if depth == 0 => return true,
+                MonkeyToken::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return false;
+                    }
+                }
+                MonkeyToken::LBrace => depth += 1,
+                _ => {}
+            }
+            self.next_token();
+        }
+    }
+
+    /// Records the error and skips the rest of the statement, returning whether the current
+    /// token is now the end of the enclosing block.
+    fn recover(&mut self, error: ParseError) -> bool {
+        self.record(error);
+        self.synchronize()
+    }
+
+    fn expect_peek(&mut self, expected: MonkeyToken) -> Result<(), ParseError> {
+        if self.peek.token == expected {
+            self.next_token();
+            Ok(())
+        } else {
+            let expected = expected.spelling().map_or_else(|| expected.literal(), String::from);
+            Err(self.unexpected(format!("`{}`", expected), &self.peek))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<Identifier, ParseError> {
+        match self.peek.token {
+            MonkeyToken::Ident(name) => {
+                self.next_token();
+                Ok(Identifier::new(name, self.current.span))
+            }
+            _ => Err(self.unexpected("an identifier", &self.peek))
+        }
+    }
+
+    /// The error for finding `token` where something else was `expected`. For an illegal token
+    /// that is the lexical error behind it.
+    fn unexpected(&self, expected: impl Into<String>, token: &LocatedToken<MonkeyToken>) -> ParseError {
+        let lexical = self.errors.iter().find(|error| matches!(error.kind, ParseErrorKind::Lexical(_))
+            && error.span.start >= token.span.start && error.span.end <= token.span.end);
+        match (&token.token, lexical) {
+            (MonkeyToken::Illegal, Some(error)) => error.clone(),
+            (MonkeyToken::EOF, _) => ParseError::expected(expected, "end of input", token.span),
+            _ => ParseError::expected(expected, format!("`{}`", &self.lexer.input()[token.span.range()]), token.span)
+        }
+    }
+
+    fn skip_semicolon(&mut self) {
+        if self.peek.token == MonkeyToken::Semicolon {
+            self.next_token();
+        }
+    }
+
+    /// Parses the statement starting at the current token. A lone `;` is an empty statement.
+    fn parse_statement(&mut self) -> Result<Option<Statement>, ParseError> {
+        match self.current.token {
+            MonkeyToken::Semicolon => Ok(None),
+            MonkeyToken::Let => self.parse_let_statement().map(Some),
+            MonkeyToken::Return => self.parse_return_statement().map(Some),
+            MonkeyToken::While => self.parse_while_statement().map(Some),
+            MonkeyToken::For => self.parse_for_statement().map(Some),
+            MonkeyToken::Break | MonkeyToken::Continue => self.parse_loop_control_statement().map(Some),
+            MonkeyToken::Ident(_) if self.peek.token == MonkeyToken::Assign => self.parse_assign_statement().map(Some),
+            _ => self.parse_expression_statement().map(Some)
+        }
+    }
+
+    fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
+        let name = self.expect_identifier()?;
+        self.expect_peek(MonkeyToken::Assign)?;
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Let { name, value })
+    }
+
+    fn parse_assign_statement(&mut self) -> Result<Statement, ParseError> {
+        let MonkeyToken::Ident(name) = self.current.token else {
+            unreachable!("assignments start with an identifier");
+        };
+        let name = Identifier::new(name, self.current.span);
+        self.next_token();
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Assign { name, value })
+    }
+
+    fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect_peek(MonkeyToken::LParen)?;
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(MonkeyToken::RParen)?;
+        self.expect_peek(MonkeyToken::LBrace)?;
+        let body = self.parse_loop_body()?;
+        self.skip_semicolon();
+        Ok(Statement::While { condition, body })
+    }
+
+    fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect_peek(MonkeyToken::LParen)?;
+        let variable = self.expect_identifier()?;
+        self.expect_peek(MonkeyToken::In)?;
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(MonkeyToken::RParen)?;
+        self.expect_peek(MonkeyToken::LBrace)?;
+        let body = self.parse_loop_body()?;
+        self.skip_semicolon();
+        Ok(Statement::For { variable, iterable, body })
+    }
+
+    fn parse_loop_body(&mut self) -> Result<BlockStatement, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_loop_control_statement(&mut self) -> Result<Statement, ParseError> {
+        let statement = if self.current.token == MonkeyToken::Break { Statement::Break } else { Statement::Continue };
+        if self.loop_depth == 0 {
+            let keyword = self.lexer.input()[self.current.span.range()].to_string();
+            return Err(ParseError::new(ParseErrorKind::OutsideLoop { keyword }, self.current.span));
+        }
+        self.skip_semicolon();
+        Ok(statement)
+    }
+
+    fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Return(value))
+    }
+
+    fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Ok(Statement::Expression(expression))
+    }
+
+    /// Parses the statements of a block, recovering from errors in them like `parse_program`
+    /// does. Only a block left open at the end of input fails.
+    fn parse_block_statement(&mut self) -> Result<BlockStatement, ParseError> {
+        let start = self.current.span;
+        let mut statements = Vec::new();
+        self.next_token();
+        while self.current.token != MonkeyToken::RBrace {
+            if self.current.token == MonkeyToken::EOF {
+                return Err(self.unexpected("`}`", &self.current));
+            }
+            match self.parse_statement() {
+                Ok(statement) => statements.extend(statement),
+                Err(error) => {
+                    if self.recover(error) {
+                        break;
+                    }
+                }
+            }
+            self.next_token();
+        }
+        Ok(BlockStatement { statements, span: start.to(self.current.span) })
+    }
+
+    /// Parses an expression whose operators all bind tighter than `precedence`. The current
+    /// token is the first token of the expression, and is its last token afterwards.
+    fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, ParseError> {
+        let mut left = self.parse_prefix()?;
+        while self.peek.token != MonkeyToken::Semicolon && precedence < precedence_of(&self.peek.token) {
+            self.next_token();
+            left = self.parse_infix(left)?;
+        }
+        Ok(left)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current.span;
+        let kind = match &self.current.token {
+            MonkeyToken::Ident(name) => ExpressionKind::Identifier(*name),
+            MonkeyToken::Int(value) => ExpressionKind::Integer(*value),
+            #[cfg(feature = "bigint")]
+            MonkeyToken::BigInt(_) => {
+                return Err(ParseError::new(ParseErrorKind::Lexical(MonkeyErrorKind::IntegerOutOfRange), start));
+            }
+            MonkeyToken::Str(value) => ExpressionKind::String(value.to_string()),
+            MonkeyToken::True => ExpressionKind::Boolean(true),
+            MonkeyToken::False => ExpressionKind::Boolean(false),
+            MonkeyToken::Bang | MonkeyToken::Minus => {
+                let operator = if self.current.token == MonkeyToken::Bang { PrefixOperator::Not } else { PrefixOperator::Negate };
+                self.next_token();
+                let right = self.parse_expression(Precedence::Prefix)?;
+                ExpressionKind::Prefix { operator, right: Box::new(right) }
+            }
+            MonkeyToken::LParen => {
+                self.next_token();
+                let expression = self.parse_expression(Precedence::Lowest)?;
+                self.expect_peek(MonkeyToken::RParen)?;
+                expression.kind
+            }
+            MonkeyToken::If => self.parse_if_expression()?,
+            MonkeyToken::Function | MonkeyToken::Macro => self.parse_function_literal()?,
+            MonkeyToken::LBracket => ExpressionKind::Array(self.parse_expression_list(MonkeyToken::RBracket)?),
+            MonkeyToken::LBrace => self.parse_hash_literal()?,
+            _ => return Err(self.unexpected("an expression", &self.current))
+        };
+        Ok(Expression::new(kind, start.to(self.current.span)))
+    }
+
+    fn parse_infix(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        let start = left.span;
+        let kind = match self.current.token {
+            MonkeyToken::LParen => {
+                let arguments = self.parse_expression_list(MonkeyToken::RParen)?;
+                ExpressionKind::Call { function: Box::new(left), arguments }
+            }
+            MonkeyToken::LBracket => {
+                self.next_token();
+                let index = self.parse_expression(Precedence::Lowest)?;
+                self.expect_peek(MonkeyToken::RBracket)?;
+                ExpressionKind::Index { left: Box::new(left), index: Box::new(index) }
+            }
+            _ => {
+                let operator = Self::infix_operator(&self.current.token)
+                    .ok_or_else(|| self.unexpected("an operator", &self.current))?;
+                let precedence = precedence_of(&self.current.token);
+                self.next_token();
+                let right = self.parse_expression(precedence)?;
+                ExpressionKind::Infix { operator, left: Box::new(left), right: Box::new(right) }
+            }
+        };
+        Ok(Expression::new(kind, start.to(self.current.span)))
+    }
+
+    fn infix_operator(token: &MonkeyToken) -> Option<InfixOperator> {
+        match token {
+            MonkeyToken::Plus => Some(InfixOperator::Add),
+            MonkeyToken::Minus => Some(InfixOperator::Subtract),
+            MonkeyToken::Asteriks => Some(InfixOperator::Multiply),
+            MonkeyToken::Slash => Some(InfixOperator::Divide),
+            MonkeyToken::Percent => Some(InfixOperator::Modulo),
+            MonkeyToken::LT => Some(InfixOperator::LessThan),
+            MonkeyToken::GT => Some(InfixOperator::GreaterThan),
+            MonkeyToken::LTE => Some(InfixOperator::LessEqual),
+            MonkeyToken::GTE => Some(InfixOperator::GreaterEqual),
+            MonkeyToken::Eq => Some(InfixOperator::Equal),
+            MonkeyToken::NotEq => Some(InfixOperator::NotEqual),
+            MonkeyToken::And => Some(InfixOperator::And),
+            MonkeyToken::Or => Some(InfixOperator::Or),
+            _ => None
+        }
+    }
+
+    fn parse_if_expression(&mut self) -> Result<ExpressionKind, ParseError> {
+        self.expect_peek(MonkeyToken::LParen)?;
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(MonkeyToken::RParen)?;
+        self.expect_peek(MonkeyToken::LBrace)?;
+        let consequence = self.parse_block_statement()?;
+        let alternative = if self.peek.token == MonkeyToken::Else {
+            self.next_token();
+            self.expect_peek(MonkeyToken::LBrace)?;
+            Some(self.parse_block_statement()?)
+        } else {
+            None
+        };
+        Ok(ExpressionKind::If { condition: Box::new(condition), consequence, alternative })
+    }
+
+    /// Parses a `fn` or `macro` literal, which only differ in the keyword.
+    fn parse_function_literal(&mut self) -> Result<ExpressionKind, ParseError> {
+        let is_macro = self.current.token == MonkeyToken::Macro;
+        self.expect_peek(MonkeyToken::LParen)?;
+        let parameters = self.parse_function_parameters()?;
+        self.expect_peek(MonkeyToken::LBrace)?;
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = body?;
+        if is_macro {
+            Ok(ExpressionKind::Macro { parameters, body })
+        } else {
+            Ok(ExpressionKind::Function { parameters, body })
+        }
+    }
+
+    fn parse_function_parameters(&mut self) -> Result<Vec<Identifier>, ParseError> {
+        let mut parameters = Vec::new();
+        if self.peek.token == MonkeyToken::RParen {
+            self.next_token();
+            return Ok(parameters);
+        }
+        parameters.push(self.expect_identifier()?);
+        while self.peek.token == MonkeyToken::Comma {
+            self.next_token();
+            parameters.push(self.expect_identifier()?);
+        }
+        self.expect_peek(MonkeyToken::RParen)?;
+        Ok(parameters)
+    }
+
+    /// Parses comma separated expressions up to `end`, starting on the opening token.
+    fn parse_expression_list(&mut self, end: MonkeyToken) -> Result<Vec<Expression>, ParseError> {
+        let mut list = Vec::new();
+        if self.peek.token == end {
+            self.next_token();
+            return Ok(list);
+        }
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+        while self.peek.token == MonkeyToken::Comma {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+        self.expect_peek(end)?;
+        Ok(list)
+    }
+
+    fn parse_hash_literal(&mut self) -> Result<ExpressionKind, ParseError> {
+        let mut pairs = Vec::new();
+        while self.peek.token != MonkeyToken::RBrace {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            self.expect_peek(MonkeyToken::Colon)?;
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+            if self.peek.token != MonkeyToken::RBrace {
+                self.expect_peek(MonkeyToken::Comma)?;
+            }
+        }
+        self.expect_peek(MonkeyToken::RBrace)?;
+        Ok(ExpressionKind::Hash(pairs))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use rstest::rstest;
+    use crate::lexer::Span;
+    use crate::monkey::ast::{Expression, ExpressionKind, InfixOperator, PrefixOperator, Program, Statement};
+    use crate::monkey::lexer::MonkeyLexer;
+    use crate::monkey::parser::Parser;
+    use crate::monkey::token::{MonkeyToken, MonkeyTokenizer};
+    use crate::symbol::Symbol;
+    use crate::token::Tokenize;
+
+    fn parse(input: &str) -> Program {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = sut.parse_program();
+
+        assert!(sut.errors().is_empty(), "unexpected parse errors: {:?}", sut.errors());
+        program
+    }
+
+    fn parse_expression(input: &str) -> Expression {
+        let mut program = parse(input);
+
+        assert_eq!(program.statements.len(), 1);
+        match program.statements.remove(0) {
+            Statement::Expression(expression) => expression,
+            statement => panic!("expected an expression statement, got {:?}", statement)
+        }
+    }
+
+    fn source(input: &str, span: Span) -> &str {
+        &input[span.range()]
+    }
+
+    fn parenthesize(expression: &Expression) -> String {
+        format!("{:#}", expression)
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let program = parse("let x = 5;\nlet y = true;\nlet foobar = y;");
+
+        let expected = [("x", "5"), ("y", "true"), ("foobar", "y")];
+        assert_eq!(program.statements.len(), expected.len());
+        for (statement, (name, value)) in program.statements.iter().zip(expected) {
+            match statement {
+                Statement::Let { name: identifier, value: expression } => {
+                    assert_eq!(identifier.name, Symbol::intern(name));
+                    assert_eq!(parenthesize(expression), value);
+                }
+                statement => panic!("expected a let statement, got {:?}", statement)
+            }
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let program = parse("return 5;\nreturn x + 10\nreturn add(1, 2);");
+
+        let expected = ["5", "(x + 10)", "add(1, 2)"];
+        assert_eq!(program.statements.len(), expected.len());
+        for (statement, value) in program.statements.iter().zip(expected) {
+            match statement {
+                Statement::Return(expression) => assert_eq!(parenthesize(expression), value),
+                statement => panic!("expected a return statement, got {:?}", statement)
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("foobar;", ExpressionKind::Identifier(Symbol::intern("foobar")))]
+    #[case("5;", ExpressionKind::Integer(5))]
+    #[case("true;", ExpressionKind::Boolean(true))]
+    #[case("false", ExpressionKind::Boolean(false))]
+    #[case("\"hello world\";", ExpressionKind::String(String::from("hello world")))]
+    fn test_literal_expressions(#[case] input: &str, #[case] expected: ExpressionKind) {
+        assert_eq!(parse_expression(input).kind, expected);
+    }
+
+    #[rstest]
+    #[case("!5;", PrefixOperator::Not, "5")]
+    #[case("-15;", PrefixOperator::Negate, "15")]
+    #[case("!true;", PrefixOperator::Not, "true")]
+    fn test_prefix_expressions(#[case] input: &str, #[case] expected_operator: PrefixOperator, #[case] expected_right: &str) {
+        match parse_expression(input).kind {
+            ExpressionKind::Prefix { operator, right } => {
+                assert_eq!(operator, expected_operator);
+                assert_eq!(parenthesize(&right), expected_right);
+            }
+            kind => panic!("expected a prefix expression, got {:?}", kind)
+        }
+    }
+
+    #[rstest]
+    #[case("5 + 5;", InfixOperator::Add)]
+    #[case("5 - 5;", InfixOperator::Subtract)]
+    #[case("5 * 5;", InfixOperator::Multiply)]
+    #[case("5 / 5;", InfixOperator::Divide)]
+    #[case("5 > 5;", InfixOperator::GreaterThan)]
+    #[case("5 < 5;", InfixOperator::LessThan)]
+    #[case("5 == 5;", InfixOperator::Equal)]
+    #[case("5 != 5;", InfixOperator::NotEqual)]
+    #[case("5 % 5;", InfixOperator::Modulo)]
+    #[case("5 <= 5;", InfixOperator::LessEqual)]
+    #[case("5 >= 5;", InfixOperator::GreaterEqual)]
+    #[case("5 && 5;", InfixOperator::And)]
+    #[case("5 || 5;", InfixOperator::Or)]
+    fn test_infix_expressions(#[case] input: &str, #[case] expected_operator: InfixOperator) {
+        match parse_expression(input).kind {
+            ExpressionKind::Infix { operator, left, right } => {
+                assert_eq!(operator, expected_operator);
+                assert_eq!(left.kind, ExpressionKind::Integer(5));
+                assert_eq!(right.kind, ExpressionKind::Integer(5));
+            }
+            kind => panic!("expected an infix expression, got {:?}", kind)
+        }
+    }
+
+    #[rstest]
+    #[case("-a * b", "((-a) * b)")]
+    #[case("!-a", "(!(-a))")]
+    #[case("a + b + c", "((a + b) + c)")]
+    #[case("a + b - c", "((a + b) - c)")]
+    #[case("a * b * c", "((a * b) * c)")]
+    #[case("a * b / c", "((a * b) / c)")]
+    #[case("a + b / c", "(a + (b / c))")]
+    #[case("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)")]
+    #[case("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))")]
+    #[case("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))")]
+    #[case("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))")]
+    #[case("true == !false", "(true == (!false))")]
+    #[case("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)")]
+    #[case("(5 + 5) * 2", "((5 + 5) * 2)")]
+    #[case("-(5 + 5)", "(-(5 + 5))")]
+    #[case("a + add(b * c) + d", "((a + add((b * c))) + d)")]
+    #[case("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))")]
+    #[case("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))")]
+    #[case("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)")]
+    #[case("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))")]
+    #[case("a + b % c * d", "(a + ((b % c) * d))")]
+    #[case("a <= b == c >= d", "((a <= b) == (c >= d))")]
+    #[case("a || b && c", "(a || (b && c))")]
+    #[case("a && b || c && d", "((a && b) || (c && d))")]
+    #[case("a == b && c != d || !e", "(((a == b) && (c != d)) || (!e))")]
+    #[case("a < b && b <= c", "((a < b) && (b <= c))")]
+    fn test_operator_precedence(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(parenthesize(&parse_expression(input)), expected);
+    }
+
+    #[test]
+    fn test_if_expression() {
+        match parse_expression("if (x < y) { x }").kind {
+            ExpressionKind::If { condition, consequence, alternative } => {
+                assert_eq!(parenthesize(&condition), "(x < y)");
+                assert_eq!(consequence.statements.len(), 1);
+                assert!(alternative.is_none());
+            }
+            kind => panic!("expected an if expression, got {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        match parse_expression("if (x < y) { x } else { let z = y; z }").kind {
+            ExpressionKind::If { alternative: Some(alternative), .. } => {
+                assert_eq!(alternative.statements.len(), 2);
+                assert!(matches!(alternative.statements[0], Statement::Let { .. }));
+            }
+            kind => panic!("expected an if expression with an alternative, got {:?}", kind)
+        }
+    }
+
+    #[rstest]
+    #[case("fn() {};", vec![], 0)]
+    #[case("fn(x) { x };", vec!["x"], 1)]
+    #[case("fn(x, y, z) { x + y; z };", vec!["x", "y", "z"], 2)]
+    fn test_function_literals(#[case] input: &str, #[case] expected_parameters: Vec<&str>, #[case] expected_statements: usize) {
+        match parse_expression(input).kind {
+            ExpressionKind::Function { parameters, body } => {
+                let names: Vec<Symbol> = parameters.iter().map(|p| p.name).collect();
+                let expected: Vec<Symbol> = expected_parameters.into_iter().map(Symbol::intern).collect();
+                assert_eq!(names, expected);
+                assert_eq!(body.statements.len(), expected_statements);
+            }
+            kind => panic!("expected a function literal, got {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_macro_literal() {
+        match parse_expression("macro(x, y) { x + y; }").kind {
+            ExpressionKind::Macro { parameters, body } => {
+                let names: Vec<Symbol> = parameters.iter().map(|p| p.name).collect();
+                assert_eq!(names, vec![Symbol::intern("x"), Symbol::intern("y")]);
+                assert_eq!(body.statements.len(), 1);
+            }
+            kind => panic!("expected a macro literal, got {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_call_expression() {
+        match parse_expression("add(1, 2 * 3, 4 + 5);").kind {
+            ExpressionKind::Call { function, arguments } => {
+                assert_eq!(function.kind, ExpressionKind::Identifier(Symbol::intern("add")));
+                let arguments: Vec<String> = arguments.iter().map(parenthesize).collect();
+                assert_eq!(arguments, vec!["1", "(2 * 3)", "(4 + 5)"]);
+            }
+            kind => panic!("expected a call expression, got {:?}", kind)
+        }
+    }
+
+    #[rstest]
+    #[case("[]", "[]")]
+    #[case("[1, 2 * 2, 3 + 3]", "[1, (2 * 2), (3 + 3)]")]
+    #[case("myArray[1 + 1]", "(myArray[(1 + 1)])")]
+    fn test_array_and_index_expressions(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(parenthesize(&parse_expression(input)), expected);
+    }
+
+    #[rstest]
+    #[case("{}", vec![])]
+    #[case(r#"{"one": 1, "two": 2, "three": 3}"#, vec![("\"one\"", "1"), ("\"two\"", "2"), ("\"three\"", "3")])]
+    #[case(r#"{"one": 0 + 1, "two": 10 - 8, "three": 15 / 5}"#, vec![("\"one\"", "(0 + 1)"), ("\"two\"", "(10 - 8)"), ("\"three\"", "(15 / 5)")])]
+    #[case("{true: 1, 2: x}", vec![("true", "1"), ("2", "x")])]
+    fn test_hash_literals(#[case] input: &str, #[case] expected: Vec<(&str, &str)>) {
+        match parse_expression(input).kind {
+            ExpressionKind::Hash(pairs) => {
+                let pairs: Vec<(String, String)> = pairs.iter().map(|(k, v)| (parenthesize(k), parenthesize(v))).collect();
+                let expected: Vec<(String, String)> = expected.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+                assert_eq!(pairs, expected);
+            }
+            kind => panic!("expected a hash literal, got {:?}", kind)
+        }
+    }
+
+    #[rstest]
+    #[case("a + b * c;", "a + b * c")]
+    #[case("  (a + b) ", "(a + b)")]
+    #[case("-x[1]", "-x[1]")]
+    #[case("f(1)(2);", "f(1)(2)")]
+    #[case("if (x) { 1 } else { 2 }", "if (x) { 1 } else { 2 }")]
+    #[case("fn(x) {\n  x\n}", "fn(x) {\n  x\n}")]
+    fn test_expression_spans(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(source(input, parse_expression(input).span), expected);
+    }
+
+    #[test]
+    fn test_spans_have_line_and_column() {
+        let expression = parse_expression("\n  foo(1,\n    2)");
+
+        assert_eq!(expression.span, Span::new(3, 16, 2, 3));
+    }
+
+    #[test]
+    fn test_comments_are_ignored() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = Parser::new(MonkeyLexer::new(tokenizer, "let x = /* five */ 5; // done").with_comments());
+        let program = sut.parse_program();
+
+        assert!(sut.errors().is_empty());
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    fn errors(input: &str) -> Vec<String> {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = Parser::new(MonkeyLexer::new(tokenizer, input));
+        sut.parse_program();
+
+        sut.errors().iter().map(|error| error.to_string()).collect()
+    }
+
+    #[rstest]
+    #[case("let = 5;", "expected an identifier but found `=` at 1:5")]
+    #[case("let x 5;", "expected `=` but found `5` at 1:7")]
+    #[case("if (x { 1 }", "expected `)` but found `{` at 1:7")]
+    #[case("fn(1) {}", "expected an identifier but found `1` at 1:4")]
+    #[case("{ 1 2 }", "expected `:` but found `2` at 1:5")]
+    #[case("fn() { 1", "expected `}` but found end of input at 1:9")]
+    #[case("let x = ;", "expected an expression but found `;` at 1:9")]
+    #[case("let fn = 1;", "expected an identifier but found `fn` at 1:5")]
+    #[case("let x = \"abc", "unterminated string literal at 1:9")]
+    #[case("let x = 5 # 3", "unexpected character '#' at 1:11")]
+    #[case("let x = 1;\nlet y = add(x,\n  2;", "expected `)` but found `;` at 3:4")]
+    fn test_errors(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(errors(input), vec![expected]);
+    }
+
+    #[test]
+    fn test_every_error_is_reported() {
+        let input = "let x 5;\nlet = 10;\nlet y = 838383;\nadd(1, 2;\nx * ;";
+
+        assert_eq!(errors(input), vec![
+            "expected `=` but found `5` at 1:7",
+            "expected an identifier but found `=` at 2:5",
+            "expected `)` but found `;` at 4:9",
+            "expected an expression but found `;` at 5:5",
+        ]);
+    }
+
+    #[rstest]
+    #[case("fn() { let = 1; x }; y", 2)]
+    #[case("if (x { let y = { 1: 2 }; } z", 1)]
+    #[case("let x = ); let y = 1;", 1)]
+    #[case("{ 1: } let y = 1;", 1)]
+    fn test_parsing_resumes_after_an_error(#[case] input: &str, #[case] expected_statements: usize) {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = Parser::new(MonkeyLexer::new(tokenizer, input));
+        let program = sut.parse_program();
+
+        assert_eq!(sut.errors().len(), 1, "{:?}", sut.errors());
+        assert_eq!(program.statements.len(), expected_statements);
+    }
+
+    #[test]
+    fn test_errors_in_blocks_keep_the_rest_of_the_block() {
+        let tokenizer: Rc<dyn Tokenize< MonkeyToken>> = Rc::new(MonkeyTokenizer::new());
+        let mut sut = Parser::new(MonkeyLexer::new(tokenizer, "fn() { let = 1; if (x) { y } z }"));
+        let program = sut.parse_program();
+
+        assert_eq!(sut.errors().len(), 1);
+        match &program.statements[..] {
+            [Statement::Expression(Expression { kind: ExpressionKind::Function { body, .. }, .. })] => {
+                assert_eq!(body.statements.len(), 2);
+            }
+            statements => panic!("expected one function literal, got {:?}", statements)
+        }
+    }
+
+    #[test]
+    fn test_assign_statement() {
+        match &parse("x = x + 1;").statements[..] {
+            [Statement::Assign { name, value }] => {
+                assert_eq!(name.name, Symbol::intern("x"));
+                assert_eq!(parenthesize(value), "(x + 1)");
+            }
+            statements => panic!("expected an assignment, got {:?}", statements)
+        }
+    }
+
+    #[test]
+    fn test_equality_is_not_assignment() {
+        assert!(matches!(parse("x == 1").statements[0], Statement::Expression(_)));
+    }
+
+    #[test]
+    fn test_while_statement() {
+        match &parse("while (i < 10) { i = i + 1; }").statements[..] {
+            [Statement::While { condition, body }] => {
+                assert_eq!(parenthesize(condition), "(i < 10)");
+                assert!(matches!(body.statements[..], [Statement::Assign { .. }]));
+            }
+            statements => panic!("expected a while loop, got {:?}", statements)
+        }
+    }
+
+    #[test]
+    fn test_for_statement() {
+        match &parse("for (x in [1, 2]) { if (x > 1) { break; } continue; }; y").statements[..] {
+            [Statement::For { variable, iterable, body }, Statement::Expression(_)] => {
+                assert_eq!(variable.name, Symbol::intern("x"));
+                assert_eq!(parenthesize(iterable), "[1, 2]");
+                assert!(matches!(body.statements[..], [Statement::Expression(_), Statement::Continue]));
+            }
+            statements => panic!("expected a for loop, got {:?}", statements)
+        }
+    }
+
+    #[rstest]
+    #[case("break;", "`break` outside of a loop at 1:1")]
+    #[case("if (x) { continue }", "`continue` outside of a loop at 1:10")]
+    #[case("while (x) { let f = fn() { break; }; }", "`break` outside of a loop at 1:28")]
+    #[case("for (x [1]) {}", "expected `in` but found `[` at 1:8")]
+    #[case("for (1 in xs) {}", "expected an identifier but found `1` at 1:6")]
+    #[case("while x {}", "expected `(` but found `x` at 1:7")]
+    #[case("x = ;", "expected an expression but found `;` at 1:5")]
+    fn test_loop_errors(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(errors(input), vec![expected]);
+    }
+
+    #[test]
+    fn test_loops_nested_in_functions_in_loops() {
+        parse("while (a) { let f = fn() { for (x in xs) { break; } }; continue; }");
+    }
+
+    #[test]
+    fn test_empty_statements() {
+        assert_eq!(parse(";; x;;").statements.len(), 1);
+    }
+}
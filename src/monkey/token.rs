@@ -1,104 +1,239 @@
-use crate::token::{Token, Tokenize};
-
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum MonkeyToken {
-    Illegal,
-    EOF,
-    Ident(String),
-    Int(isize),
-    Assign,
-    Plus,
-    Minus,
-    Bang,
-    Asteriks,
-    Slash,
-    LT,
-    GT,
-    Eq,
-    NotEq,
-    Comma,
-    Semicolon,
-    LParen,
-    RParen,
-    LBrace,
-    RBrace,
-    Function,
-    Let,
-    True,
-    False,
-    If,
-    Else,
-    Return
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+use crate::symbol::Symbol;
+use crate::define_tokens;
+use crate::token::{CharacterSet, LexicalErrorKind, Tokenize};
+
+define_tokens! {
+    pub enum MonkeyToken<'a>: MonkeyErrorKind {
+        values {
+            Comment(&'a str),
+            Ident(Symbol),
+            Int(isize),
+            Str(Cow<'a, str>),
+            #[cfg(feature = "bigint")]
+            BigInt(BigInt),
+        }
+        symbols {
+            Assign = "=",
+            Plus = "+",
+            Minus = "-",
+            Bang = "!",
+            Asteriks = "*",
+            Slash = "/",
+            Percent = "%",
+            LT = "<",
+            GT = ">",
+            LTE = "<=",
+            GTE = ">=",
+            Eq = "==",
+            NotEq = "!=",
+            And = "&&",
+            Or = "||",
+            Comma = ",",
+            Colon = ":",
+            Semicolon = ";",
+            LParen = "(",
+            RParen = ")",
+            LBrace = "{",
+            RBrace = "}",
+            LBracket = "[",
+            RBracket = "]",
+        }
+        keywords {
+            Function = "fn" => "FUNCTION",
+            Let = "let" => "LET",
+            True = "true" => "TRUE",
+            False = "false" => "FALSE",
+            If = "if" => "IF",
+            Else = "else" => "ELSE",
+            Return = "return" => "RETURN",
+            While = "while" => "WHILE",
+            For = "for" => "FOR",
+            In = "in" => "IN",
+            Break = "break" => "BREAK",
+            Continue = "continue" => "CONTINUE",
+            Macro = "macro" => "MACRO",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonkeyErrorKind {
+    UnexpectedCharacter(char),
+    IntegerOutOfRange,
+    UnterminatedComment,
+    UnterminatedString,
+    InvalidEscape { escape: String, at: usize },
 }
 
-impl Token for MonkeyToken {
-    fn literal(&self) -> String {
+impl fmt::Display for MonkeyErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            MonkeyToken::Illegal => String::from("ILLEGAL"),
-            MonkeyToken::EOF => String::from("EOF"),
-            MonkeyToken::Ident(ident) => String::from(ident),
-            MonkeyToken::Int(value) => String::from(value.to_string().as_str()),
-            MonkeyToken::Assign => String::from("="),
-            MonkeyToken::Plus => String::from("+"),
-            MonkeyToken::Minus => String::from("-"),
-            MonkeyToken::Bang => String::from("!"),
-            MonkeyToken::Asteriks => String::from("*"),
-            MonkeyToken::Slash => String::from("/"),
-            MonkeyToken::LT => String::from("<"),
-            MonkeyToken::GT => String::from(">"),
-            MonkeyToken::Eq => String::from("=="),
-            MonkeyToken::NotEq => String::from("!="),
-            MonkeyToken::Comma => String::from(","),
-            MonkeyToken::Semicolon => String::from(";"),
-            MonkeyToken::LParen => String::from("("),
-            MonkeyToken::RParen => String::from(")"),
-            MonkeyToken::LBrace => String::from("{"),
-            MonkeyToken::RBrace => String::from("}"),
-            MonkeyToken::Function => String::from("FUNCTION"),
-            MonkeyToken::Let => String::from("LET"),
-            MonkeyToken::True => String::from("TRUE"),
-            MonkeyToken::False => String::from("FALSE"),
-            MonkeyToken::If => String::from("IF"),
-            MonkeyToken::Else => String::from("ELSE"),
-            MonkeyToken::Return => String::from("RETURN"),
+            MonkeyErrorKind::UnexpectedCharacter(ch) => write!(f, "unexpected character '{}'", ch),
+            MonkeyErrorKind::IntegerOutOfRange => write!(f, "integer literal out of range"),
+            MonkeyErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            MonkeyErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            MonkeyErrorKind::InvalidEscape { escape, .. } => write!(f, "invalid escape sequence '{}'", escape),
         }
     }
 }
 
-pub struct MonkeyTokenizer {
+impl LexicalErrorKind for MonkeyErrorKind {
+    fn unexpected_character(ch: char) -> Self {
+        MonkeyErrorKind::UnexpectedCharacter(ch)
+    }
+
+    fn range_in_lexeme(&self) -> Option<Range<usize>> {
+        match self {
+            MonkeyErrorKind::InvalidEscape { escape, at } => Some(*at..*at + escape.len()),
+            _ => None
+        }
+    }
+}
 
+#[derive(Debug, Clone, Default)]
+pub struct MonkeyTokenizer {
+    character_set: CharacterSet,
+    legacy_identifiers: bool,
 }
 
 impl MonkeyTokenizer {
+    pub fn new() -> MonkeyTokenizer {
+        MonkeyTokenizer::default()
+    }
+
+    pub fn with_character_set(mut self, character_set: CharacterSet) -> MonkeyTokenizer {
+        self.character_set = character_set;
+        self
+    }
+
+    /// Only allows letters and `_` in identifiers, so `x1` lexes as `x` followed by `1`,
+    /// as earlier versions of the tokenizer did.
+    pub fn with_legacy_identifiers(mut self) -> MonkeyTokenizer {
+        self.legacy_identifiers = true;
+        self
+    }
+
     fn is_digit(ch: char) -> bool{
-        '0' <= ch && ch <= '9'
+        ch.is_ascii_digit()
+    }
+
+    fn is_letter(&self, ch: char) -> bool{
+        self.character_set.is_identifier_start(ch)
+    }
+
+    fn is_closed_block_comment(s: &str) -> bool {
+        s.len() >= 4 && s.ends_with("*/")
+    }
+
+    /// Whether `s` is a complete string literal, ending in a quote that is not escaped.
+    fn is_closed_string(s: &str) -> bool {
+        s.len() >= 2 && s.ends_with('"') && s[1..s.len() - 1].chars().rev().take_while(|&ch| ch == '\\').count() % 2 == 0
+    }
+
+    /// The text of a string literal with its escape sequences replaced. The text is borrowed
+    /// from the input unless it contains escapes.
+    fn to_string_literal(s: &str) -> Result<MonkeyToken<'_>, MonkeyErrorKind> {
+        if !Self::is_closed_string(s) {
+            return Err(MonkeyErrorKind::UnterminatedString);
+        }
+        let content = &s[1..s.len() - 1];
+        if !content.contains('\\') {
+            return Ok(MonkeyToken::Str(Cow::Borrowed(content)));
+        }
+        let mut value = String::with_capacity(content.len());
+        let mut rest = content;
+        while let Some(backslash) = rest.find('\\') {
+            value.push_str(&rest[..backslash]);
+            let escape = &rest[backslash..];
+            match Self::unescape(escape) {
+                Ok((ch, length)) => {
+                    value.push(ch);
+                    rest = &escape[length..];
+                }
+                Err(length) => {
+                    let at = s.len() - escape.len() - 1;
+                    return Err(MonkeyErrorKind::InvalidEscape { escape: escape[..length].to_string(), at });
+                }
+            }
+        }
+        value.push_str(rest);
+        Ok(MonkeyToken::Str(Cow::Owned(value)))
+    }
+
+    /// Decodes the escape sequence at the start of `escape`, returning the character and
+    /// the length of the sequence, or the length of the invalid sequence.
+    fn unescape(escape: &str) -> Result<(char, usize), usize> {
+        match escape[1..].chars().next() {
+            Some('n') => Ok(('\n', 2)),
+            Some('t') => Ok(('\t', 2)),
+            Some('"') => Ok(('"', 2)),
+            Some('\\') => Ok(('\\', 2)),
+            Some('u') => {
+                if !escape[2..].starts_with('{') {
+                    return Err(2);
+                }
+                let digits = escape[3..].chars().take_while(|ch| ch.is_ascii_hexdigit()).count();
+                let end = 3 + digits;
+                if !escape[end..].starts_with('}') {
+                    return Err(end);
+                }
+                u32::from_str_radix(&escape[3..end], 16).ok()
+                    .filter(|_| (1..=6).contains(&digits))
+                    .and_then(char::from_u32)
+                    .map(|ch| (ch, end + 1))
+                    .ok_or(end + 1)
+            }
+            Some(ch) => Err(1 + ch.len_utf8()),
+            None => Err(1)
+        }
     }
 
-    fn is_letter(ch: char) -> bool{
-        'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_'
+    fn is_number(s: &str) -> bool {
+        !s.is_empty() && s.chars().all(Self::is_digit)
+    }
+
+    /// Numeric lexemes are always numbers; a value that does not fit in an `isize` is an
+    /// error, or a `BigInt` token when the `bigint` feature is enabled.
+    fn to_number(s: &str) -> Result<MonkeyToken<'static>, MonkeyErrorKind> {
+        match s.parse::<isize>() {
+            Ok(value) => Ok(MonkeyToken::Int(value)),
+            #[cfg(feature = "bigint")]
+            Err(_) => Ok(MonkeyToken::BigInt(s.parse().expect("a numeric lexeme is a valid integer"))),
+            #[cfg(not(feature = "bigint"))]
+            Err(_) => Err(MonkeyErrorKind::IntegerOutOfRange),
+        }
     }
 }
-impl Tokenize<MonkeyToken> for MonkeyTokenizer {
+impl<'a> Tokenize<'a, MonkeyToken<'a>> for MonkeyTokenizer {
     fn is_whitespace_character(&self, ch: char) -> bool {
-        ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r'
+        self.character_set.is_whitespace(ch)
     }
 
-    fn is_symbol_start_character(&self, ch: char, _ : char) -> bool {
-        ch == '=' || ch == '+' || ch == '-' || ch == '!' ||
-            ch == '/' || ch == '*' || ch == '<' || ch == '>' ||
-            ch == ';' || ch == '(' || ch == ')' || ch == '{' ||
-            ch == '}' || ch == ','
+    fn is_comment_start_character(&self, ch: char, next: char) -> bool {
+        ch == '/' && (next == '/' || next == '*')
     }
 
-    fn is_symbol_part_character(&self, ch: char, _:char, s: &str) -> bool {
-        match s {
-            "=" => ch == '=',
-            "!" => ch == '=',
-            _ => false
+    fn is_comment_part_character(&self, ch: char, next: char, s: &str) -> bool {
+        if s.starts_with("//") {
+            ch != '\n' && ch != '\0' && !(ch == '\r' && next == '\n')
+        } else {
+            ch != '\0' && !MonkeyTokenizer::is_closed_block_comment(s)
         }
     }
 
+    fn is_string_start_character(&self, ch: char, _ : char) -> bool {
+        ch == '"'
+    }
+
+    fn is_string_part_character(&self, ch: char, _ : char, s: &str) -> bool {
+        ch != '\0' && !MonkeyTokenizer::is_closed_string(s)
+    }
+
     fn is_numeric_start_character(&self, ch: char, _ : char) -> bool {
         MonkeyTokenizer::is_digit(ch)
     }
@@ -108,53 +243,32 @@ impl Tokenize<MonkeyToken> for MonkeyTokenizer {
     }
 
     fn is_identifier_start_character(&self, ch: char, _ : char) -> bool {
-        MonkeyTokenizer::is_letter(ch)
+        self.is_letter(ch)
     }
 
     fn is_identifier_part_character(&self, ch: char, _:char, _: &str) -> bool {
-        MonkeyTokenizer::is_letter(ch)
-    }
-
-    fn to_token(&self, s: &str) -> MonkeyToken {
-        match s {
-            "==" => MonkeyToken::Eq,
-            "=" => MonkeyToken::Assign,
-            "!=" => MonkeyToken::NotEq,
-            "!" => MonkeyToken::Bang,
-            "+" => MonkeyToken::Plus,
-            "-" => MonkeyToken::Minus,
-            "/" => MonkeyToken::Slash,
-            "*" => MonkeyToken::Asteriks,
-            "<" => MonkeyToken::LT,
-            ">" => MonkeyToken::GT,
-            ";" => MonkeyToken::Semicolon,
-            "(" => MonkeyToken::LParen,
-            ")" => MonkeyToken::RParen,
-            "{" => MonkeyToken::LBrace,
-            "}" => MonkeyToken::RBrace,
-            "," => MonkeyToken::Comma,
-            "fn" => MonkeyToken::Function,
-            "let" => MonkeyToken::Let,
-            "true" => MonkeyToken::True,
-            "false" => MonkeyToken::False,
-            "if" => MonkeyToken::If,
-            "else" => MonkeyToken::Else,
-            "return" => MonkeyToken::Return,
-            x => {
-                if let Ok(value) = x.parse::<isize>() {
-                    MonkeyToken::Int(value)
-                } else {
-                    MonkeyToken::Ident(x.to_string())
-                }
-            }
+        if self.legacy_identifiers {
+            self.is_letter(ch)
+        } else {
+            self.character_set.is_identifier_continue(ch)
         }
     }
 
-    fn end_of_file_token(&self) -> MonkeyToken {
-        MonkeyToken::EOF
+    fn to_value_token(&self, s: &'a str) -> Result<MonkeyToken<'a>, MonkeyErrorKind> {
+        if s.starts_with('"') {
+            Self::to_string_literal(s)
+        } else if Self::is_number(s) {
+            Self::to_number(s)
+        } else {
+            Ok(MonkeyToken::Ident(Symbol::intern(s)))
+        }
     }
 
-    fn error_token(&self) -> MonkeyToken {
-        MonkeyToken::Illegal
+    fn to_comment_token(&self, s: &'a str) -> Result<MonkeyToken<'a>, MonkeyErrorKind> {
+        if s.starts_with("/*") && !MonkeyTokenizer::is_closed_block_comment(s) {
+            Err(MonkeyErrorKind::UnterminatedComment)
+        } else {
+            Ok(MonkeyToken::Comment(s))
+        }
     }
 }
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use crate::symbol::Symbol;
+
+/// Maps names to `Symbol` ids and back. Each distinct name is stored once, for the rest of
+/// the process, since the symbols handed out for it may live as long.
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    names: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol::from_index(self.names.len());
+        let name: &'static str = Box::leak(Box::from(name));
+        self.names.push(name);
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The name of a symbol interned by this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &'static str {
+        self.names[symbol.index()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::symbol::interner::Interner;
+
+    #[test]
+    fn test_interner() {
+        let mut sut = Interner::default();
+        let x = sut.intern("x");
+        let y = sut.intern("y");
+
+        assert_eq!(sut.intern("x"), x);
+        assert_ne!(x, y);
+        assert_eq!(sut.resolve(y), "y");
+    }
+}
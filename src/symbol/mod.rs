@@ -0,0 +1,5 @@
+#[allow(clippy::module_inception)]
+mod symbol;
+mod interner;
+
+pub use self::symbol::Symbol;
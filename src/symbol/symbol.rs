@@ -0,0 +1,73 @@
+use std::fmt;
+use std::sync::{LazyLock, PoisonError, RwLock};
+use crate::symbol::interner::Interner;
+
+/// The names of all symbols, shared by every thread so that a symbol means the same name
+/// wherever it is used.
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+/// An interned name. Symbols are shared by the lexer, parser and evaluator, so comparing
+/// and hashing names costs the same as for an integer.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.write().unwrap_or_else(PoisonError::into_inner).intern(name)
+    }
+
+    /// The name the symbol was interned from.
+    pub fn name(&self) -> &'static str {
+        INTERNER.read().unwrap_or_else(PoisonError::into_inner).resolve(*self)
+    }
+
+    pub(crate) fn from_index(index: usize) -> Symbol {
+        Symbol(u32::try_from(index).expect("too many symbols"))
+    }
+
+    pub(crate) fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use crate::symbol::Symbol;
+
+    #[test]
+    fn test_same_name_same_symbol() {
+        assert_eq!(Symbol::intern("monkey"), Symbol::intern("monkey"));
+        assert_ne!(Symbol::intern("monkey"), Symbol::intern("banana"));
+    }
+
+    #[test]
+    fn test_name() {
+        let symbol = Symbol::intern("længde");
+
+        assert_eq!(symbol.name(), "længde");
+        assert_eq!(symbol.to_string(), "længde");
+        assert_eq!(format!("{:?}", symbol), "Symbol(\"længde\")");
+    }
+
+    #[test]
+    fn test_symbols_mean_the_same_on_every_thread() {
+        let here = Symbol::intern("here");
+        let (there, name) = thread::spawn(move || (Symbol::intern("here"), here.name())).join().unwrap();
+
+        assert_eq!(there, here);
+        assert_eq!(name, "here");
+    }
+}
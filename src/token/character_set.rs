@@ -0,0 +1,36 @@
+use unicode_ident::{is_xid_continue, is_xid_start};
+
+/// The characters a tokenizer treats as whitespace and allows in identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharacterSet {
+    /// Space, tab, `\n` and `\r` are whitespace; identifiers are ASCII letters, digits and `_`.
+    Ascii,
+    /// Unicode `White_Space` is whitespace; identifiers follow `XID_Start` and `XID_Continue`.
+    #[default]
+    Unicode,
+}
+
+impl CharacterSet {
+    pub fn is_whitespace(&self, ch: char) -> bool {
+        match self {
+            CharacterSet::Ascii => ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r',
+            CharacterSet::Unicode => ch.is_whitespace(),
+        }
+    }
+
+    /// Whether `ch` can start an identifier. `_` always can.
+    pub fn is_identifier_start(&self, ch: char) -> bool {
+        ch == '_' || match self {
+            CharacterSet::Ascii => ch.is_ascii_alphabetic(),
+            CharacterSet::Unicode => is_xid_start(ch),
+        }
+    }
+
+    /// Whether `ch` can continue an identifier. `_` always can.
+    pub fn is_identifier_continue(&self, ch: char) -> bool {
+        ch == '_' || match self {
+            CharacterSet::Ascii => ch.is_ascii_alphanumeric(),
+            CharacterSet::Unicode => is_xid_continue(ch),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+/// Declares a token enum from one table of its value carrying variants, symbols and
+/// keywords, and implements `Token` for it. `Illegal` and `EOF` are always added.
+///
+/// The `Tokenize` trait reads the symbol and keyword tables through `Token`, so a tokenizer
+/// only has to describe whitespace, comments, numbers and identifiers, and the text a token
+/// is lexed from can never drift apart from its `literal()`.
+///
+/// ```ignore
+/// define_tokens! {
+///     pub enum CalcToken: CalcErrorKind {
+///         values { Int(isize) }
+///         symbols { Plus = "+", Minus = "-" }
+///         keywords { Print = "print" => "PRINT" }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_tokens {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident $(<$lt:lifetime>)? : $error:ty {
+            values {
+                $( $(#[$value_attr:meta])* $value:ident($value_type:ty) ),* $(,)?
+            }
+            symbols {
+                $( $symbol:ident = $symbol_text:literal ),* $(,)?
+            }
+            keywords {
+                $( $keyword:ident = $keyword_text:literal => $keyword_literal:literal ),* $(,)?
+            }
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq)]
+        #[allow(clippy::upper_case_acronyms)]
+        $vis enum $name $(<$lt>)? {
+            Illegal,
+            EOF,
+            $( $(#[$value_attr])* $value($value_type), )*
+            $( $symbol, )*
+            $( $keyword, )*
+        }
+
+        impl $(<$lt>)? $crate::token::Token for $name $(<$lt>)? {
+            type ErrorKind = $error;
+
+            fn literal(&self) -> String {
+                match self {
+                    $name::Illegal => String::from("ILLEGAL"),
+                    $name::EOF => String::from("EOF"),
+                    $( $(#[$value_attr])* $name::$value(value) => value.to_string(), )*
+                    $( $name::$symbol => String::from($symbol_text), )*
+                    $( $name::$keyword => String::from($keyword_literal), )*
+                }
+            }
+
+            fn spelling(&self) -> Option<&'static str> {
+                match self {
+                    $( $name::$symbol => Some($symbol_text), )*
+                    $( $name::$keyword => Some($keyword_text), )*
+                    _ => None
+                }
+            }
+
+            fn keyword(s: &str) -> Option<Self> {
+                match s {
+                    $( $keyword_text => Some($name::$keyword), )*
+                    _ => None
+                }
+            }
+
+            fn symbol(s: &str) -> Option<Self> {
+                match s {
+                    $( $symbol_text => Some($name::$symbol), )*
+                    _ => None
+                }
+            }
+
+            fn symbols() -> &'static [&'static str] {
+                &[ $( $symbol_text ),* ]
+            }
+
+            fn end_of_file() -> Self {
+                $name::EOF
+            }
+
+            fn illegal() -> Self {
+                $name::Illegal
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+    use std::rc::Rc;
+    use crate::lexer::{Lexer, LocatedToken, Span};
+    use crate::token::{LexicalErrorKind, Token, Tokenize};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum CalcErrorKind {
+        UnexpectedCharacter(char),
+        UnknownWord(String),
+    }
+
+    impl fmt::Display for CalcErrorKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                CalcErrorKind::UnexpectedCharacter(ch) => write!(f, "unexpected character '{}'", ch),
+                CalcErrorKind::UnknownWord(word) => write!(f, "unknown word '{}'", word),
+            }
+        }
+    }
+
+    impl LexicalErrorKind for CalcErrorKind {
+        fn unexpected_character(ch: char) -> Self {
+            CalcErrorKind::UnexpectedCharacter(ch)
+        }
+    }
+
+    define_tokens! {
+        enum CalcToken: CalcErrorKind {
+            values {
+                Int(isize),
+            }
+            symbols {
+                Plus = "+",
+                Power = "**",
+                PowerAssign = "**=",
+                Times = "*",
+                Arrow = "->",
+                Dot = ".",
+                Ellipsis = "...",
+            }
+            keywords {
+                Print = "print" => "PRINT",
+            }
+        }
+    }
+
+    struct CalcTokenizer {}
+
+    impl<'a> Tokenize<'a, CalcToken> for CalcTokenizer {
+        fn is_whitespace_character(&self, ch: char) -> bool {
+            ch == ' '
+        }
+
+        fn is_comment_start_character(&self, _: char, _: char) -> bool {
+            false
+        }
+
+        fn is_comment_part_character(&self, _: char, _: char, _: &str) -> bool {
+            false
+        }
+
+        fn is_numeric_start_character(&self, ch: char, _: char) -> bool {
+            ch.is_ascii_digit()
+        }
+
+        fn is_numeric_part_character(&self, ch: char, _: char, _: &str) -> bool {
+            ch.is_ascii_digit()
+        }
+
+        fn is_identifier_start_character(&self, ch: char, _: char) -> bool {
+            ch.is_ascii_lowercase()
+        }
+
+        fn is_identifier_part_character(&self, ch: char, _: char, _: &str) -> bool {
+            ch.is_ascii_lowercase()
+        }
+
+        fn to_value_token(&self, s: &'a str) -> Result<CalcToken, CalcErrorKind> {
+            s.parse().map(CalcToken::Int).map_err(|_| CalcErrorKind::UnknownWord(s.to_string()))
+        }
+
+        fn to_comment_token(&self, s: &'a str) -> Result<CalcToken, CalcErrorKind> {
+            Err(CalcErrorKind::UnknownWord(s.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_tables() {
+        assert_eq!(CalcToken::keyword("print"), Some(CalcToken::Print));
+        assert_eq!(CalcToken::keyword("+"), None);
+        assert_eq!(CalcToken::symbol("**"), Some(CalcToken::Power));
+        assert_eq!(CalcToken::symbol("print"), None);
+        assert_eq!(CalcToken::symbols(), &["+", "**", "**=", "*", "->", ".", "..."]);
+        assert_eq!(CalcToken::end_of_file(), CalcToken::EOF);
+        assert_eq!(CalcToken::illegal(), CalcToken::Illegal);
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(CalcToken::Int(42).literal(), "42");
+        assert_eq!(CalcToken::Power.literal(), "**");
+        assert_eq!(CalcToken::Print.literal(), "PRINT");
+        assert_eq!(CalcToken::EOF.literal(), "EOF");
+        assert_eq!(CalcToken::Illegal.literal(), "ILLEGAL");
+    }
+
+    #[test]
+    fn test_spellings() {
+        assert_eq!(CalcToken::Power.spelling(), Some("**"));
+        assert_eq!(CalcToken::Print.spelling(), Some("print"));
+        assert_eq!(CalcToken::Int(42).spelling(), None);
+        assert_eq!(CalcToken::EOF.spelling(), None);
+    }
+
+    #[test]
+    fn test_lexing_with_generated_tables() {
+        let tokenizer: Rc<dyn Tokenize<CalcToken>> = Rc::new(CalcTokenizer{});
+        let mut sut = Lexer::new(tokenizer, "print 2 ** 3 * 4 + x");
+        let tokens: Vec<CalcToken> = sut.by_ref().map(|t| t.token).collect();
+
+        assert_eq!(tokens, vec![
+            CalcToken::Print,
+            CalcToken::Int(2),
+            CalcToken::Power,
+            CalcToken::Int(3),
+            CalcToken::Times,
+            CalcToken::Int(4),
+            CalcToken::Plus,
+            CalcToken::Illegal,
+            CalcToken::EOF
+        ]);
+        assert_eq!(sut.errors()[0].message(), "unknown word 'x'");
+    }
+
+    #[test]
+    fn test_longest_symbol_wins() {
+        let tokenizer: Rc<dyn Tokenize<CalcToken>> = Rc::new(CalcTokenizer{});
+        let tokens: Vec<CalcToken> = Lexer::new(tokenizer, "2**=3 *** ... ..")
+            .map(|t| t.token)
+            .collect();
+
+        assert_eq!(tokens, vec![
+            CalcToken::Int(2),
+            CalcToken::PowerAssign,
+            CalcToken::Int(3),
+            CalcToken::Power,
+            CalcToken::Times,
+            CalcToken::Ellipsis,
+            CalcToken::Dot,
+            CalcToken::Dot,
+            CalcToken::EOF
+        ]);
+    }
+
+    #[test]
+    fn test_backtracking_keeps_spans() {
+        let tokenizer: Rc<dyn Tokenize<CalcToken>> = Rc::new(CalcTokenizer{});
+        let mut sut = Lexer::new(tokenizer, "..1 -");
+
+        assert_eq!(sut.next_token(), LocatedToken::new(CalcToken::Dot, Span::new(0, 1, 1, 1)));
+        assert_eq!(sut.next_token(), LocatedToken::new(CalcToken::Dot, Span::new(1, 2, 1, 2)));
+        assert_eq!(sut.next_token(), LocatedToken::new(CalcToken::Int(1), Span::new(2, 3, 1, 3)));
+        assert_eq!(sut.next_token(), LocatedToken::new(CalcToken::Illegal, Span::new(4, 5, 1, 5)));
+        assert_eq!(sut.next_token(), LocatedToken::new(CalcToken::EOF, Span::new(5, 5, 1, 6)));
+    }
+}
@@ -1,4 +1,9 @@
+#[allow(clippy::module_inception)]
 mod token;
+mod macros;
+mod character_set;
 
 pub use self::token::Token;
 pub use self::token::Tokenize;
+pub use self::token::LexicalErrorKind;
+pub use self::character_set::CharacterSet;
@@ -1,18 +1,92 @@
-pub trait Token {
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+
+pub trait Token: Sized {
+    /// The lexical errors a language can report while tokenizing.
+    type ErrorKind: LexicalErrorKind;
+
     fn literal(&self) -> String;
+
+    /// How a symbol or keyword is written in source, `None` for other tokens.
+    fn spelling(&self) -> Option<&'static str>;
+
+    /// The keyword spelled `s`, if any.
+    fn keyword(s: &str) -> Option<Self>;
+
+    /// The symbol spelled `s`, if any.
+    fn symbol(s: &str) -> Option<Self>;
+
+    /// The spelling of every symbol.
+    fn symbols() -> &'static [&'static str];
+
+    fn end_of_file() -> Self;
+    fn illegal() -> Self;
+}
+
+/// A language specific kind of lexical error. `Display` gives the error message.
+pub trait LexicalErrorKind: Debug + Display + Clone + PartialEq {
+    /// The error reported for a character that cannot start any token.
+    fn unexpected_character(ch: char) -> Self;
+
+    /// The byte range of the lexeme the error is about, when it is only part of it,
+    /// such as one bad escape sequence in a string literal.
+    fn range_in_lexeme(&self) -> Option<Range<usize>> {
+        None
+    }
 }
 
-pub trait Tokenize<T>
+/// Tokenizes input borrowed for `'a`, so tokens can refer to slices of it instead of copying.
+pub trait Tokenize<'a, T>
 where T: Token {
     fn is_whitespace_character(&self, ch: char) -> bool;
-    fn is_symbol_start_character(&self, ch: char, next: char) -> bool;
-    fn is_symbol_part_character(&self, ch: char, next: char, s: &str) -> bool;
+    fn is_comment_start_character(&self, ch: char, next: char) -> bool;
+    fn is_comment_part_character(&self, ch: char, next: char, s: &str) -> bool;
+
+    fn is_symbol_start_character(&self, ch: char, _next: char) -> bool {
+        T::symbols().iter().any(|symbol| symbol.starts_with(ch))
+    }
+
+    fn is_symbol_part_character(&self, ch: char, _next: char, s: &str) -> bool {
+        T::symbols().iter().any(|symbol| symbol.strip_prefix(s).is_some_and(|rest| rest.starts_with(ch)))
+    }
+
+    /// Whether `s` is a complete symbol. The lexer reads the longest run of symbol characters
+    /// and then backs off to the longest prefix of it for which this holds.
+    fn is_symbol(&self, s: &str) -> bool {
+        T::symbol(s).is_some()
+    }
+
+    fn is_string_start_character(&self, _ch: char, _next: char) -> bool {
+        false
+    }
+
+    fn is_string_part_character(&self, _ch: char, _next: char, _s: &str) -> bool {
+        false
+    }
+
     fn is_numeric_start_character(&self, ch: char, next:char) -> bool;
     fn is_numeric_part_character(&self, ch: char, next: char, s: &str) -> bool;
     fn is_identifier_start_character(&self, ch: char, next: char) -> bool;
     fn is_identifier_part_character(&self, ch: char, next: char, s: &str) -> bool;
-    fn to_token(&self, s: &str) -> T;
 
-    fn end_of_file_token(&self) -> T;
-    fn error_token(&self) -> T;
+    /// Turns a lexeme into a token, looking it up in the token's symbol and keyword tables
+    /// before handing it to `to_value_token`.
+    fn to_token(&self, s: &'a str) -> Result<T, T::ErrorKind> {
+        match T::symbol(s).or_else(|| T::keyword(s)) {
+            Some(token) => Ok(token),
+            None => self.to_value_token(s)
+        }
+    }
+
+    /// Turns a lexeme that is neither a symbol nor a keyword into a token.
+    fn to_value_token(&self, s: &'a str) -> Result<T, T::ErrorKind>;
+    fn to_comment_token(&self, s: &'a str) -> Result<T, T::ErrorKind>;
+
+    fn end_of_file_token(&self) -> T {
+        T::end_of_file()
+    }
+
+    fn error_token(&self) -> T {
+        T::illegal()
+    }
 }